@@ -0,0 +1,99 @@
+//! Compare the sparse set component storage with the former `HashMap<u32, C>` layout
+//!
+//! The join runs through `WorldState::query` and reports the share of it spent building
+//! the per iteration row table of `&mut` queries. Run with `cargo bench --bench storage`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use dragon::core::{Transform3, Vector3};
+use dragon::ecs::{Component, Entity, SparseSet, TransformComponent, World};
+
+const ENTITIES: u32 = 10_000;
+const ROUNDS: u32 = 200;
+
+struct Mesh;
+impl Component for Mesh {}
+
+fn measure<F: FnMut()>(name: &str, mut f: F) -> Duration {
+    // Warm up caches and the allocator before timing
+    for _ in 0..10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let elapsed = start.elapsed() / ROUNDS;
+    println!("{:<40} {:>10.2?}/iter", name, elapsed);
+    elapsed
+}
+
+fn main() {
+    let step = Vector3::new(0., 0., 0.1);
+
+    let mut map_transforms: HashMap<u32, Transform3> = HashMap::new();
+    let mut map_meshes: HashMap<u32, u32> = HashMap::new();
+    let mut set_transforms: SparseSet<Transform3> = SparseSet::new();
+    let mut set_meshes: SparseSet<u32> = SparseSet::new();
+    for index in 0..ENTITIES {
+        let entity = Entity::new(index, 0);
+        map_transforms.insert(index, Transform3::default());
+        set_transforms.insert(entity, Transform3::default());
+        // Every other entity carries a mesh to exercise the join
+        if index % 2 == 0 {
+            map_meshes.insert(index, index);
+            set_meshes.insert(entity, index);
+        }
+    }
+
+    println!("{} entities, {} rounds", ENTITIES, ROUNDS);
+    let map = measure("HashMap iterate transforms", || {
+        for transform in map_transforms.values_mut() {
+            transform.prepend_translation(step);
+        }
+        black_box(&map_transforms);
+    });
+    let set = measure("SparseSet iterate transforms", || {
+        for transform in set_transforms.values_mut() {
+            transform.prepend_translation(step);
+        }
+        black_box(&set_transforms);
+    });
+    println!("{:<40} {:>10.2}x", "speedup", map.as_secs_f64() / set.as_secs_f64());
+
+    // Both joins visit the same entities, those carrying a mesh, in handle order
+    let mut joined: Vec<u32> = map_meshes.keys().copied().collect();
+    joined.sort_unstable();
+    let world = World::new();
+    for index in 0..ENTITIES {
+        if index % 2 == 0 {
+            world.state.spawn((TransformComponent::default(), Mesh));
+        } else {
+            world.state.spawn((TransformComponent::default(),));
+        }
+    }
+
+    let map = measure("HashMap join transforms + meshes", || {
+        for id in joined.iter() {
+            if let (Some(transform), Some(_)) = (map_transforms.get_mut(id), map_meshes.get(id)) {
+                transform.prepend_translation(step);
+            }
+        }
+        black_box(&map_transforms);
+    });
+    let set = measure("Query join transforms + meshes", || {
+        let mut query = world.state.query::<(&mut TransformComponent, &Mesh)>().unwrap();
+        for (_, (mut transform, _)) in query.iter() {
+            transform.prepend_translation(step);
+        }
+    });
+    println!("{:<40} {:>10.2}x", "speedup", map.as_secs_f64() / set.as_secs_f64());
+
+    let mut query = world.state.query::<(&mut TransformComponent, &Mesh)>().unwrap();
+    let rows = measure("&mut row table per query iteration", || {
+        black_box(query.iter());
+    });
+    println!("{:<40} {:>10.1}%", "share of the query join", rows.as_secs_f64() / set.as_secs_f64() * 100.);
+}
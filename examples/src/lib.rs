@@ -150,8 +150,7 @@ impl Application {
         let world_span = wand::WorldSpan::new(state.clone(), app.context.clone(), "world", "World", 1., 1.);
         let w = &world_span.world.state;
         {
-            // Attach cube entity
-            let entity = world_span.world.state.create_entity();
+            // Register the wire cube once, then spawn it with its own transform
             let vertices = [
                 (-2., -2., 4.,),
                 (2., -2., 4.),
@@ -174,12 +173,12 @@ impl Application {
                 .into_iter()
                 .map(|v| core::Point3::new(v.0, v.1, v.2)).collect();
             let mesh = core::BasicMesh::new(vertices, vec!(9, 11, 13));
+            w.register_prefab("wire_cube", ecs::Prefab::new().with(mesh));
             let mut transform = ecs::TransformComponent::default();
             transform.set_translation_xyz(-5., 0., -16.);
-            w.bind_component(entity, mesh);
-            w.bind_component(entity, transform);
-            let movement_system = movement::MovementSystem::new(w.clone(), app.input.clone());
-            w.register_system("movement", movement_system);
+            w.spawn_prefab("wire_cube", (transform,)).unwrap();
+            let movement_system = movement::MovementSystem::new(app.input.clone());
+            w.register_system("movement", movement_system).unwrap();
         }
 
         // Add a simple mesh entity
@@ -217,8 +216,6 @@ impl Application {
             transform.set_translation_xyz(5., 0., -16.);
             w.bind_component(entity, mesh);
             w.bind_component(entity, transform);
-            let movement_system = movement::MovementSystem::new(w.clone(), app.input.clone());
-            w.register_system("movement", movement_system);
         }
 
         {
@@ -1,5 +1,7 @@
 use crate::core::*;
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub enum Camera {
     Orthographic {
         projection: Orthographic3<f32>,
@@ -9,10 +11,171 @@ pub enum Camera {
     }
 }
 
+impl Default for Camera {
+    /// 16:9 perspective with a fovy of pi/8, seeing from 1 to 10000 units
+    fn default() -> Self {
+        Self::perspective(16. / 9., std::f32::consts::PI / 8., 1., 10000.).unwrap()
+    }
+}
+
+/// Finite and positive, as required for aspects, extents and zoom factors
+fn is_positive(value: f32) -> bool {
+    value > 0. && value.is_finite()
+}
+
+/// Finite planes with the far one beyond the near one, as orthographic cameras require
+fn is_depth_range(znear: f32, zfar: f32) -> bool {
+    znear.is_finite() && zfar.is_finite() && zfar - znear > f32::EPSILON
+}
+
+/// Depth range with the near plane in front of the eye, as perspective cameras require
+fn is_perspective_range(znear: f32, zfar: f32) -> bool {
+    znear > 0. && is_depth_range(znear, zfar)
+}
+
 impl Camera {
-    pub fn default() -> Self {
-        Camera::Perspective {
-            projection: Perspective3::new(16./9., 3.14/8., 1000., 10000.),
+    /// `None` unless the aspect is positive, the fovy lies in `(0, pi)` and `0 < znear < zfar`
+    pub fn perspective(aspect: f32, fovy: f32, znear: f32, zfar: f32) -> Option<Self> {
+        let fovy_valid = fovy > 0. && fovy < std::f32::consts::PI;
+        if !is_positive(aspect) || !fovy_valid || !is_perspective_range(znear, zfar) {
+            return None;
+        }
+        Some(Camera::Perspective {
+            projection: Perspective3::new(aspect, fovy, znear, zfar),
+        })
+    }
+
+    /// Box centered on the view axis, `height` world units tall and `height * aspect` wide
+    ///
+    /// `None` unless the aspect and height are positive and `znear < zfar`.
+    pub fn orthographic(aspect: f32, height: f32, znear: f32, zfar: f32) -> Option<Self> {
+        if !is_positive(aspect) || !is_positive(height) || !is_depth_range(znear, zfar) {
+            return None;
+        }
+        let (x, y) = (height * aspect / 2., height / 2.);
+        Some(Camera::Orthographic {
+            projection: Orthographic3::new(-x, x, -y, y, znear, zfar),
+        })
+    }
+
+    /// Width over height of the view
+    pub fn aspect(&self) -> f32 {
+        match self {
+            Camera::Orthographic { ref projection } => (projection.right() - projection.left()) / (projection.top() - projection.bottom()),
+            Camera::Perspective { ref projection } => projection.aspect(),
+        }
+    }
+
+    /// Vertical field of view, `None` for orthographic cameras
+    pub fn fov(&self) -> Option<f32> {
+        match self {
+            Camera::Orthographic { .. } => None,
+            Camera::Perspective { ref projection } => Some(projection.fovy()),
+        }
+    }
+
+    /// Visible height in world units, `None` for perspective cameras
+    pub fn ortho_extent(&self) -> Option<f32> {
+        match self {
+            Camera::Orthographic { ref projection } => Some(projection.top() - projection.bottom()),
+            Camera::Perspective { .. } => None,
+        }
+    }
+
+    pub fn znear(&self) -> f32 {
+        match self {
+            Camera::Orthographic { ref projection } => projection.znear(),
+            Camera::Perspective { ref projection } => projection.znear(),
+        }
+    }
+
+    pub fn zfar(&self) -> f32 {
+        match self {
+            Camera::Orthographic { ref projection } => projection.zfar(),
+            Camera::Perspective { ref projection } => projection.zfar(),
+        }
+    }
+
+    /// Follow a viewport resize, orthographic cameras keep their height and center
+    ///
+    /// Does nothing unless the aspect is finite and positive.
+    pub fn set_aspect(&mut self, aspect: f32) -> &mut Self {
+        if !is_positive(aspect) {
+            return self;
+        }
+        match self {
+            Camera::Orthographic { ref mut projection } => {
+                let center = (projection.left() + projection.right()) / 2.;
+                let half = (projection.top() - projection.bottom()) * aspect / 2.;
+                projection.set_left_and_right(center - half, center + half);
+            }
+            Camera::Perspective { ref mut projection } => projection.set_aspect(aspect),
+        }
+        self
+    }
+
+    /// Set the vertical field of view, ignored by orthographic cameras
+    ///
+    /// Does nothing unless the fovy lies in `(0, pi)`.
+    pub fn set_fov(&mut self, fovy: f32) -> &mut Self {
+        if let Camera::Perspective { ref mut projection } = self {
+            if fovy > 0. && fovy < std::f32::consts::PI {
+                projection.set_fovy(fovy);
+            }
+        }
+        self
+    }
+
+    /// Move the clipping planes
+    ///
+    /// Does nothing unless the planes are finite with `znear < zfar`, perspective cameras
+    /// also need `znear > 0`.
+    pub fn set_near_far(&mut self, znear: f32, zfar: f32) -> &mut Self {
+        match self {
+            Camera::Orthographic { ref mut projection } if is_depth_range(znear, zfar) => {
+                projection.set_znear_and_zfar(znear, zfar)
+            }
+            Camera::Perspective { ref mut projection } if is_perspective_range(znear, zfar) => {
+                projection.set_znear_and_zfar(znear, zfar)
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Set the visible height around the current center, ignored by perspective cameras
+    ///
+    /// Does nothing unless the height is finite and positive.
+    pub fn set_ortho_extent(&mut self, height: f32) -> &mut Self {
+        if let Camera::Orthographic { ref mut projection } = self {
+            if is_positive(height) {
+                let aspect = (projection.right() - projection.left()) / (projection.top() - projection.bottom());
+                let (x, y) = ((projection.left() + projection.right()) / 2., (projection.bottom() + projection.top()) / 2.);
+                projection.set_left_and_right(x - height * aspect / 2., x + height * aspect / 2.);
+                projection.set_bottom_and_top(y - height / 2., y + height / 2.);
+            }
+        }
+        self
+    }
+
+    /// Magnify the view by `factor`, values below 1 zoom out
+    ///
+    /// Perspective cameras narrow their field of view, orthographic ones their extent. Does
+    /// nothing unless the factor is finite and positive.
+    pub fn zoom(&mut self, factor: f32) -> &mut Self {
+        if !is_positive(factor) {
+            return self;
+        }
+        match self {
+            Camera::Orthographic { .. } => {
+                let extent = self.ortho_extent().unwrap_or(1.);
+                self.set_ortho_extent(extent / factor)
+            }
+            Camera::Perspective { ref projection } => {
+                // Scale the half angle tangent so the image scales linearly with the factor
+                let fovy = 2. * ((projection.fovy() / 2.).tan() / factor).atan();
+                self.set_fov(fovy)
+            }
         }
     }
 
@@ -45,6 +208,69 @@ impl Camera {
     }
 }
 
+/// View and projection of a camera placed in the world, see `WorldState::get_active_camera`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraView {
+    /// World to camera space, the inverse of the camera world transform
+    pub view: Matrix4<f32>,
+    pub projection: Matrix4<f32>,
+    pub view_projection: Matrix4<f32>,
+}
+
+impl CameraView {
+    /// `None` if the camera transform can not be inverted
+    pub fn new(camera: &Camera, transform: &Matrix4<f32>) -> Option<Self> {
+        let view = transform.try_inverse()?;
+        let projection = *camera.as_matrix();
+        Some(Self {
+            view,
+            projection,
+            view_projection: projection * view,
+        })
+    }
+
+    /// Screen position in pixels with the normalized depth as z
+    ///
+    /// `None` for points behind the camera or outside the near/far range.
+    pub fn world_to_screen(&self, point: &Point3<f32>, viewport: &Viewport) -> Option<Point3<f32>> {
+        Self::clip_to_screen(self.view_projection * point.to_homogeneous(), viewport)
+    }
+
+    /// Same as `world_to_screen` for a point already in camera space
+    pub fn view_to_screen(&self, point: &Point3<f32>, viewport: &Viewport) -> Option<Point3<f32>> {
+        Self::clip_to_screen(self.projection * point.to_homogeneous(), viewport)
+    }
+
+    fn clip_to_screen(clip: Vector4<f32>, viewport: &Viewport) -> Option<Point3<f32>> {
+        if clip.w <= 0. {
+            return None;
+        }
+        let ndc = clip.xyz() / clip.w;
+        if ndc.z < -1. || ndc.z > 1. {
+            return None;
+        }
+        let screen = viewport.to_screen(ndc.x, ndc.y);
+        Some(Point3::new(screen.x, screen.y, ndc.z))
+    }
+
+    /// World point under the pixel at the normalized depth, -1 is the near and 1 the far plane
+    pub fn screen_to_world(&self, screen: &Point2<f32>, depth: f32, viewport: &Viewport) -> Option<Point3<f32>> {
+        let ndc = viewport.to_ndc(screen.x, screen.y);
+        let world = self.view_projection.try_inverse()? * Vector4::new(ndc.x, ndc.y, depth, 1.);
+        if world.w == 0. {
+            return None;
+        }
+        Some(Point3::from(world.xyz() / world.w))
+    }
+
+    /// Ray from the near plane through the pixel, for picking
+    pub fn screen_ray(&self, screen: &Point2<f32>, viewport: &Viewport) -> Option<(Point3<f32>, Unit<Vector3<f32>>)> {
+        let near = self.screen_to_world(screen, -1., viewport)?;
+        let far = self.screen_to_world(screen, 1., viewport)?;
+        Unit::try_new(far - near, f32::EPSILON).map(|direction| (near, direction))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -57,5 +283,54 @@ mod tests {
         assert_eq!(size / distance, camera.transform_size(size, distance));
         println!("{}", camera.transform_size(size, distance));
     }
+
+    #[test]
+    fn test_resize_and_zoom() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        let mut camera = Camera::perspective(1., std::f32::consts::FRAC_PI_2, 1., 100.).unwrap();
+        camera.set_aspect(2.).set_near_far(0.5, 50.).set_near_far(3., 3.).set_near_far(1., f32::NAN);
+        camera.set_near_far(0., 10.).set_near_far(-1., 10.).set_near_far(10., 1.);
+        assert!(close(camera.aspect(), 2.) && close(camera.znear(), 0.5) && close(camera.zfar(), 50.));
+        camera.zoom(2.);
+        assert!(close(camera.fov().unwrap(), 2. * 0.5f32.atan()));
+        camera.set_fov(4.);
+        assert!(close(camera.fov().unwrap(), 2. * 0.5f32.atan()));
+
+        let mut camera = Camera::orthographic(2., 10., 0.1, 100.).unwrap();
+        assert!(close(camera.aspect(), 2.) && close(camera.ortho_extent().unwrap(), 10.));
+        camera.zoom(4.).set_aspect(1.5);
+        assert!(close(camera.aspect(), 1.5) && close(camera.ortho_extent().unwrap(), 2.5));
+        assert!(close(camera.project_point(&Point3::new(1.875, 0., -1.)).x, 1.));
+        assert_eq!(camera.fov(), None);
+        camera.set_near_far(10., 1.).set_near_far(-5., 5.);
+        assert!(close(camera.znear(), -5.) && close(camera.zfar(), 5.));
+
+        assert!(Camera::perspective(0., 1., 1., 100.).is_none());
+        assert!(Camera::perspective(1., 1., 10., 10.).is_none());
+        assert!(Camera::perspective(1., 1., 0., 100.).is_none());
+        assert!(Camera::perspective(1., 1., 100., 1.).is_none());
+        assert!(Camera::orthographic(1., 10., 100., 1.).is_none());
+        assert!(Camera::orthographic(1., 10., -1., 1.).is_some());
+        assert!(Camera::orthographic(1., f32::NAN, 1., 100.).is_none());
+        assert!(Camera::orthographic(1., 10., 1., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_camera_view_round_trip() {
+        let camera = Camera::Perspective { projection: Perspective3::new(2., std::f32::consts::FRAC_PI_2, 1., 100.) };
+        let transform = Translation3::new(0., 0., 10.).to_homogeneous();
+        let view = CameraView::new(&camera, &transform).unwrap();
+        let viewport = Viewport::new(200., 100.);
+
+        let screen = view.world_to_screen(&Point3::new(0., 5., 0.), &viewport).unwrap();
+        assert!((screen.x - 100.).abs() < 1e-3 && (screen.y - 25.).abs() < 1e-3);
+        let world = view.screen_to_world(&Point2::new(screen.x, screen.y), screen.z, &viewport).unwrap();
+        assert!((world - Point3::new(0., 5., 0.)).norm() < 1e-3);
+        assert!(view.world_to_screen(&Point3::new(0., 0., 20.), &viewport).is_none());
+
+        let (origin, direction) = view.screen_ray(&Point2::new(100., 50.), &viewport).unwrap();
+        assert!((origin - Point3::new(0., 0., 9.)).norm() < 1e-3);
+        assert!((direction.into_inner() - Vector3::new(0., 0., -1.)).norm() < 1e-3);
+    }
 }
  
@@ -0,0 +1,118 @@
+use crate::core::{Point2, Point3};
+
+/// Size in pixels of the surface a `DrawList` is projected onto, kept as a world resource
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self { width, height }
+    }
+
+    #[inline]
+    pub fn aspect(&self) -> f32 {
+        self.width / self.height
+    }
+
+    /// Map normalized device coordinates to pixels, y grows downwards
+    #[inline]
+    pub fn to_screen(&self, x: f32, y: f32) -> Point2<f32> {
+        Point2::new((x + 1.) * 0.5 * self.width, (1. - y) * 0.5 * self.height)
+    }
+
+    /// Inverse of `to_screen`
+    #[inline]
+    pub fn to_ndc(&self, x: f32, y: f32) -> Point2<f32> {
+        Point2::new(x / self.width * 2. - 1., 1. - y / self.height * 2.)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(800., 600.)
+    }
+}
+
+/// Screen space shape, colors are CSS style names left to the backend
+///
+/// Lines and triangles keep the normalized device depth of each vertex in z.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Primitive {
+    Line {
+        from: Point3<f32>,
+        to: Point3<f32>,
+        stroke: Option<String>,
+    },
+    Triangle {
+        points: [Point3<f32>; 3],
+        fill: Option<String>,
+    },
+    Polygon {
+        points: Vec<Point2<f32>>,
+        fill: Option<String>,
+        stroke: Option<String>,
+    },
+    Circle {
+        center: Point2<f32>,
+        radius: f32,
+        fill: Option<String>,
+        stroke: Option<String>,
+    },
+    Text {
+        position: Point2<f32>,
+        text: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawItem {
+    /// Normalized device depth used for sorting, larger is further away
+    pub depth: f32,
+    pub primitive: Primitive,
+}
+
+/// Backend neutral primitives of one frame, written by `RenderingSystem`
+///
+/// Items are sorted back to front so drawing them in order paints correctly.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DrawList {
+    pub viewport: Viewport,
+    items: Vec<DrawItem>,
+}
+
+impl DrawList {
+    pub fn new(viewport: Viewport) -> Self {
+        Self {
+            viewport,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, depth: f32, primitive: Primitive) {
+        self.items.push(DrawItem { depth, primitive });
+    }
+
+    /// Order items back to front, ties keep their insertion order
+    pub fn sort(&mut self) {
+        self.items.sort_by(|a, b| b.depth.partial_cmp(&a.depth).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    pub fn items(&self) -> &[DrawItem] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
@@ -14,18 +14,39 @@ pub enum MeshRecipe<'a> {
 
 pub type Mesh = Box<dyn MeshProto>;
 
-pub trait MeshProto {
-    fn cook(&self) -> MeshRecipe;
+pub trait MeshProto: MeshClone {
+    fn cook(&self) -> MeshRecipe<'_>;
+}
+
+/// Clone behind the box, lets a `Mesh` be copied into prefabs
+///
+/// Implemented for every `Clone` mesh, implementors of `MeshProto` only derive `Clone`.
+pub trait MeshClone {
+    fn box_clone(&self) -> Mesh;
+}
+
+impl<T: 'static + MeshProto + Clone> MeshClone for T {
+    fn box_clone(&self) -> Mesh {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Mesh {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
 }
 
 /// MeshBasic only draw lines 
 #[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicMesh {
     pub vertices: Vec<Point3<f32>>,
     pub breaks: Vec<usize>,
 }
 
 impl BasicMesh {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(vertices: Vec<Point3<f32>>, breaks: Vec<usize>) -> Mesh {
         Box::new(Self {
             vertices,
@@ -35,13 +56,14 @@ impl BasicMesh {
 }
 
 impl MeshProto for BasicMesh {
-    fn cook(&self) -> MeshRecipe {
-        MeshRecipe::Basic { data: &self }
+    fn cook(&self) -> MeshRecipe<'_> {
+        MeshRecipe::Basic { data: self }
     }
 }
 
 /// MeshPolygon draw triangles 
 #[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct SimpleMesh {
     pub vertices: Vec<Point3<f32>>,
     // Point index + Color
@@ -50,6 +72,7 @@ pub struct SimpleMesh {
 
 impl SimpleMesh {
 
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(vertices: Vec<Point3<f32>>, polygons: Vec<(usize, usize, usize, String)>) -> Mesh {
         Box::new(Self {
             vertices,
@@ -59,12 +82,13 @@ impl SimpleMesh {
 }
 
 impl MeshProto for SimpleMesh {
-    fn cook(&self) -> MeshRecipe {
-        MeshRecipe::Simple { data: &self }
+    fn cook(&self) -> MeshRecipe<'_> {
+        MeshRecipe::Simple { data: self }
     }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub enum Brush {
     Lines {
         stroke: Option<String>,
@@ -95,7 +119,8 @@ pub enum Brush {
 
 
 /// ComplexMesh draw common shapes
-#[derive(Clone)]
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComplexMesh {
     pub brushes: Vec<Brush>,
 }
@@ -109,8 +134,8 @@ impl ComplexMesh {
 }
 
 impl MeshProto for ComplexMesh {
-    fn cook(&self) -> MeshRecipe {
-        MeshRecipe::Complex { data: &self }
+    fn cook(&self) -> MeshRecipe<'_> {
+        MeshRecipe::Complex { data: self }
     }
 }
 
@@ -3,17 +3,21 @@ pub mod mesh;
 pub mod shape;
 pub mod transform;
 pub mod widget;
+pub mod draw;
+pub mod raster;
 
 pub use nalgebra::{
     self as na,
     Vector3, Point3, Perspective3, Orthographic3, Isometry3, Translation3, UnitQuaternion, Matrix4, Unit,
-    Vector2, Point2, Isometry2,
+    Vector2, Point2, Isometry2, Vector4,
 };
 
-pub use camera::Camera;
+pub use camera::{Camera, CameraView};
 pub use mesh::{Mesh, MeshRecipe, BasicMesh, SimpleMesh, ComplexMesh, Brush};
 pub use transform::Transform3;
 pub use shape::Shape;
 pub use widget::Widget;
+pub use draw::{DrawList, DrawItem, Primitive, Viewport};
+pub use raster::{Framebuffer, Rgba, parse_color};
 
 
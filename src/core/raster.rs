@@ -0,0 +1,397 @@
+use crate::core::{Point2, Point3};
+
+/// Straight RGBA color, alpha 255 is opaque
+pub type Rgba = [u8; 4];
+
+/// Parse a CSS style color: `#rgb`, `#rrggbb`, `#rrggbbaa`, `rgb(..)`, `rgba(..)` or a basic name
+pub fn parse_color(text: &str) -> Option<Rgba> {
+    let text = text.trim().to_ascii_lowercase();
+    if let Some(hex) = text.strip_prefix('#') {
+        let digit = |i: usize| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok();
+        let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return match hex.len() {
+            3 => Some([digit(0)? * 17, digit(1)? * 17, digit(2)? * 17, 255]),
+            6 => Some([byte(0)?, byte(2)?, byte(4)?, 255]),
+            8 => Some([byte(0)?, byte(2)?, byte(4)?, byte(6)?]),
+            _ => None,
+        };
+    }
+    if let Some(args) = text.strip_prefix("rgba(").or_else(|| text.strip_prefix("rgb(")) {
+        let args: Vec<&str> = args.strip_suffix(')')?.split(',').map(str::trim).collect();
+        let channel = |i: usize| args[i].parse::<u8>().ok();
+        return match args.len() {
+            3 => Some([channel(0)?, channel(1)?, channel(2)?, 255]),
+            4 => Some([channel(0)?, channel(1)?, channel(2)?, (args[3].parse::<f32>().ok()?.clamp(0., 1.) * 255.).round() as u8]),
+            _ => None,
+        };
+    }
+    let rgb = match text.as_str() {
+        "transparent" => return Some([0, 0, 0, 0]),
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "red" => [255, 0, 0],
+        "lime" => [0, 255, 0],
+        "green" => [0, 128, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "cyan" | "aqua" => [0, 255, 255],
+        "magenta" | "fuchsia" => [255, 0, 255],
+        "gray" | "grey" => [128, 128, 128],
+        "silver" => [192, 192, 192],
+        "maroon" => [128, 0, 0],
+        "olive" => [128, 128, 0],
+        "navy" => [0, 0, 128],
+        "purple" => [128, 0, 128],
+        "teal" => [0, 128, 128],
+        "orange" => [255, 165, 0],
+        "pink" => [255, 192, 203],
+        "brown" => [165, 42, 42],
+        _ => return None,
+    };
+    Some([rgb[0], rgb[1], rgb[2], 255])
+}
+
+/// In-memory RGBA image with a depth buffer, the target of `RasterSystem`
+///
+/// Writes are depth tested with `<=` so later primitives at the same depth win. Lines and
+/// polygons take screen x/y with the depth in z, interpolated per pixel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    color: Vec<u8>,
+    depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            color: vec![0; width * height * 4],
+            depth: vec![f32::INFINITY; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Row major RGBA bytes, top row first
+    pub fn as_rgba(&self) -> &[u8] {
+        &self.color
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Rgba> {
+        self.index(x, y).map(|i| [self.color[i * 4], self.color[i * 4 + 1], self.color[i * 4 + 2], self.color[i * 4 + 3]])
+    }
+
+    pub fn depth(&self, x: usize, y: usize) -> Option<f32> {
+        self.index(x, y).map(|i| self.depth[i])
+    }
+
+    /// Fill with the color and reset the depth buffer
+    pub fn clear(&mut self, color: Rgba) {
+        for pixel in self.color.chunks_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+        for depth in self.depth.iter_mut() {
+            *depth = f32::INFINITY;
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    /// Depth test and blend one pixel, coordinates outside the image and non-finite depths are ignored
+    pub fn plot(&mut self, x: i64, y: i64, depth: f32, color: Rgba) {
+        if x < 0 || y < 0 || !depth.is_finite() {
+            return;
+        }
+        let i = match self.index(x as usize, y as usize) {
+            Some(i) => i,
+            None => return,
+        };
+        if depth > self.depth[i] || color[3] == 0 {
+            return;
+        }
+        self.depth[i] = depth;
+        let alpha = u32::from(color[3]);
+        for (old, new) in self.color[i * 4..i * 4 + 3].iter_mut().zip(color.iter()) {
+            *old = ((u32::from(*new) * alpha + u32::from(*old) * (255 - alpha)) / 255) as u8;
+        }
+        self.color[i * 4 + 3] = self.color[i * 4 + 3].max(color[3]);
+    }
+
+    /// Line clipped to the image first, so far off-screen endpoints cost nothing
+    pub fn draw_line(&mut self, from: Point3<f32>, to: Point3<f32>, color: Rgba) {
+        let (from, to) = match self.clip_line(from, to) {
+            Some(clipped) => clipped,
+            None => return,
+        };
+        let delta = to - from;
+        let steps = delta.x.abs().max(delta.y.abs()).ceil().max(1.) as usize;
+        for step in 0..=steps {
+            let point = from + delta * (step as f32 / steps as f32);
+            self.plot(point.x.floor() as i64, point.y.floor() as i64, point.z, color);
+        }
+    }
+
+    /// Part of the segment inside `[0, width] x [0, height]` (Liang–Barsky), computed in f64
+    /// so endpoints far off-screen keep their precision
+    fn clip_line(&self, from: Point3<f32>, to: Point3<f32>) -> Option<(Point3<f32>, Point3<f32>)> {
+        if !from.coords.iter().chain(to.coords.iter()).all(|v| v.is_finite()) {
+            return None;
+        }
+        let from = from.coords.map(f64::from);
+        let delta = to.coords.map(f64::from) - from;
+        let (mut enter, mut exit) = (0f64, 1f64);
+        let edges = [
+            (-delta.x, from.x),
+            (delta.x, self.width as f64 - from.x),
+            (-delta.y, from.y),
+            (delta.y, self.height as f64 - from.y),
+        ];
+        for (p, q) in edges.iter() {
+            if *p == 0. {
+                // Parallel to the edge, either fully outside or never crossing it
+                if *q < 0. {
+                    return None;
+                }
+            } else if *p < 0. {
+                enter = enter.max(q / p);
+            } else {
+                exit = exit.min(q / p);
+            }
+        }
+        if enter <= exit {
+            let at = |t: f64| Point3::from((from + delta * t).map(|v| v as f32));
+            Some((at(enter), at(exit)))
+        } else {
+            None
+        }
+    }
+
+    /// Outline through the points, closing back to the first one
+    pub fn stroke_polygon(&mut self, points: &[Point3<f32>], color: Rgba) {
+        for (i, from) in points.iter().enumerate() {
+            self.draw_line(*from, points[(i + 1) % points.len()], color);
+        }
+    }
+
+    /// Even-odd scanline fill sampled at pixel centers, depth is interpolated along the edges and spans
+    pub fn fill_polygon(&mut self, points: &[Point3<f32>], color: Rgba) {
+        if points.len() < 3 {
+            return;
+        }
+        let top = points.iter().map(|p| p.y).fold(f32::INFINITY, f32::min).floor().max(0.) as usize;
+        let bottom = points.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max).ceil().min(self.height as f32);
+        let mut crossings: Vec<(f32, f32)> = Vec::new();
+        for y in top..bottom.max(0.) as usize {
+            let center = y as f32 + 0.5;
+            crossings.clear();
+            for (i, a) in points.iter().enumerate() {
+                let b = points[(i + 1) % points.len()];
+                if (a.y <= center) != (b.y <= center) {
+                    let t = (center - a.y) / (b.y - a.y);
+                    crossings.push((a.x + t * (b.x - a.x), a.z + t * (b.z - a.z)));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            for span in crossings.chunks(2).filter(|span| span.len() == 2) {
+                let ((left, near), (right, far)) = (span[0], span[1]);
+                let start = (left - 0.5).ceil().max(0.) as i64;
+                let end = (right - 0.5).floor().min(self.width as f32) as i64;
+                let slope = if right > left { (far - near) / (right - left) } else { 0. };
+                for x in start..=end {
+                    self.plot(x, y as i64, near + (x as f32 + 0.5 - left) * slope, color);
+                }
+            }
+        }
+    }
+
+    pub fn fill_circle(&mut self, center: Point2<f32>, radius: f32, depth: f32, color: Rgba) {
+        self.circle(center, radius, depth, color, |distance| distance <= radius);
+    }
+
+    pub fn stroke_circle(&mut self, center: Point2<f32>, radius: f32, depth: f32, color: Rgba) {
+        self.circle(center, radius + 1., depth, color, |distance| (distance - radius).abs() <= 0.5);
+    }
+
+    fn circle<F: Fn(f32) -> bool>(&mut self, center: Point2<f32>, extent: f32, depth: f32, color: Rgba, covers: F) {
+        let (left, right) = ((center.x - extent).floor() as i64, (center.x + extent).ceil() as i64);
+        let (top, bottom) = ((center.y - extent).floor() as i64, (center.y + extent).ceil() as i64);
+        for y in top.max(0)..bottom.min(self.height as i64) {
+            for x in left.max(0)..right.min(self.width as i64) {
+                let offset = Point2::new(x as f32 + 0.5, y as f32 + 0.5) - center;
+                if covers(offset.norm()) {
+                    self.plot(x, y, depth, color);
+                }
+            }
+        }
+    }
+
+    /// Binary PPM (P6), alpha is dropped
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in self.color.chunks(4) {
+            data.extend_from_slice(&pixel[..3]);
+        }
+        data
+    }
+
+    /// 8 bit RGBA PNG, the image data is stored without compression
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut scanlines = Vec::with_capacity((self.width * 4 + 1) * self.height);
+        for row in self.color.chunks(self.width * 4).filter(|row| !row.is_empty()) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(if blocks.peek().is_none() { 1 } else { 0 });
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + u32::from(*byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("red"), Some([255, 0, 0, 255]));
+        assert_eq!(parse_color("#0f8"), Some([0, 255, 136, 255]));
+        assert_eq!(parse_color("#10203040"), Some([16, 32, 48, 64]));
+        assert_eq!(parse_color("rgba(1, 2, 3, 0.5)"), Some([1, 2, 3, 128]));
+        assert_eq!(parse_color("nope"), None);
+    }
+
+    #[test]
+    fn test_fill_and_depth() {
+        let mut image = Framebuffer::new(8, 8);
+        image.clear([0, 0, 0, 255]);
+        let square = |depth: f32| [
+            Point3::new(2., 2., depth), Point3::new(6., 2., depth), Point3::new(6., 6., depth), Point3::new(2., 6., depth),
+        ];
+        image.fill_polygon(&square(0.5), [255, 0, 0, 255]);
+        image.fill_polygon(&square(0.9), [0, 255, 0, 255]);
+        assert_eq!(image.pixel(3, 3), Some([255, 0, 0, 255]));
+        assert_eq!(image.pixel(6, 6), Some([0, 0, 0, 255]));
+        assert_eq!(image.depth(2, 5), Some(0.5));
+
+        image.plot(3, 3, f32::NAN, [0, 0, 255, 255]);
+        assert_eq!(image.pixel(3, 3), Some([255, 0, 0, 255]));
+        assert_eq!(image.depth(3, 3), Some(0.5));
+    }
+
+    #[test]
+    fn test_line_clipping() {
+        let mut image = Framebuffer::new(8, 8);
+        image.draw_line(Point3::new(-1e9, 3.5, 0.5), Point3::new(1e9, 3.5, 0.5), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(0, 3), Some([255, 0, 0, 255]));
+        assert_eq!(image.pixel(7, 3), Some([255, 0, 0, 255]));
+        assert_eq!(image.pixel(7, 4), Some([0, 0, 0, 0]));
+
+        let before = image.clone();
+        image.draw_line(Point3::new(-4., -1., 0.5), Point3::new(20., -1., 0.5), [0, 255, 0, 255]);
+        image.draw_line(Point3::new(-4., 12., 0.5), Point3::new(12., 9., 0.5), [0, 255, 0, 255]);
+        image.draw_line(Point3::new(f32::NAN, 1., 0.5), Point3::new(4., 4., 0.5), [0, 255, 0, 255]);
+        assert_eq!(image, before);
+    }
+
+    #[test]
+    fn test_crossing_triangles() {
+        // Red runs from near on the left to far on the right, blue the other way round
+        let mut image = Framebuffer::new(8, 8);
+        let red = [Point3::new(0., 0., 0.2), Point3::new(8., 0., 0.8), Point3::new(0., 16., 0.2)];
+        let blue = [Point3::new(0., 0., 0.8), Point3::new(8., 0., 0.2), Point3::new(8., 16., 0.2)];
+        image.fill_polygon(&red, [255, 0, 0, 255]);
+        image.fill_polygon(&blue, [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 1), Some([255, 0, 0, 255]));
+        assert_eq!(image.pixel(6, 1), Some([0, 0, 255, 255]));
+        assert!((image.depth(1, 1).unwrap() - 0.3125).abs() < 1e-6);
+
+        let mut image = Framebuffer::new(8, 8);
+        image.draw_line(Point3::new(0., 2.5, 0.2), Point3::new(8., 2.5, 0.8), [255, 0, 0, 255]);
+        image.draw_line(Point3::new(0., 2.5, 0.8), Point3::new(8., 2.5, 0.2), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 2), Some([255, 0, 0, 255]));
+        assert_eq!(image.pixel(6, 2), Some([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_encodings() {
+        let mut image = Framebuffer::new(2, 1);
+        image.clear([1, 2, 3, 255]);
+        assert_eq!(image.to_ppm(), b"P6\n2 1\n255\n\x01\x02\x03\x01\x02\x03".to_vec());
+
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        let png = image.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+    }
+}
@@ -1,6 +1,8 @@
 use crate::core::Point3;
 use crate::core::Transform3;
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub enum Shape {
     Line {
         begin: Point3<f32>,
@@ -3,11 +3,22 @@ use crate::core::*;
 pub struct Transform2d {
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub struct Transform3 {
     isometry: Isometry3<f32>, 
     scale: Vector3<f32>,
 }
 
+impl Default for Transform3 {
+    fn default() -> Self {
+        Self {
+            isometry: Isometry3::identity(),
+            scale: Vector3::from_element(1.),
+        }
+    }
+}
+
 impl Transform3 {
     pub fn new(
         position: Translation3<f32>,
@@ -20,12 +31,6 @@ impl Transform3 {
         }
     }
 
-    pub fn default() -> Self {
-        Self {
-            isometry: Isometry3::identity(),
-            scale: Vector3::from_element(1.),
-        }
-    }
 
     #[inline]
     pub fn translation(&self) -> &Vector3<f32> {
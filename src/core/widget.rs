@@ -1,5 +1,7 @@
 use crate::core::Vector3;
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub enum Widget {
     Text {
         translation: Vector3<f32>,
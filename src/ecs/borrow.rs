@@ -0,0 +1,184 @@
+use std::any::Any;
+use std::cell::{BorrowError, Ref, RefCell, RefMut};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::ecs::component::ComponentTicks;
+
+/// Shared borrow of the outer store a nested guard was taken from
+pub(crate) struct Outer<'w> {
+    _borrow: Ref<'w, dyn Any>,
+}
+
+/// Borrow `cell` and hand out its content for the full lifetime `'w`
+///
+/// # Safety
+///
+/// The returned reference is only valid while the returned `Outer` is alive,
+/// anything derived from it must be dropped before the `Outer`.
+pub(crate) unsafe fn hold<O: 'static>(cell: &RefCell<O>) -> Result<(Outer<'_>, &O), BorrowError> {
+    let outer = cell.try_borrow()?;
+    let inner = &*(&*outer as *const O);
+    Ok((Outer { _borrow: Ref::map(outer, |outer| outer as &dyn Any) }, inner))
+}
+
+/// Shared borrow of a value living in one of the world stores
+pub struct StoreRef<'w, T: ?Sized> {
+    // Declared before `_outer` so it is released first
+    value: Ref<'w, T>,
+    _outer: Outer<'w>,
+}
+
+/// Exclusive borrow of a value living in one of the world stores
+pub struct StoreRefMut<'w, T: ?Sized> {
+    // Declared before `_outer` so it is released first
+    value: RefMut<'w, T>,
+    _outer: Outer<'w>,
+}
+
+/// Exclusive access to a component that marks it as changed when written through
+///
+/// Returned by `&mut C` queries and `WorldState::get_component_mut`. Reading through it
+/// leaves `Changed<C>` untouched.
+pub struct Mut<'a, C: ?Sized> {
+    value: Slot<'a, C>,
+    ticks: Slot<'a, ComponentTicks>,
+    tick: u64,
+}
+
+/// Row handed out by a query table or a storage borrow of its own
+enum Slot<'a, T: ?Sized> {
+    Row(&'a mut T),
+    Store(StoreRefMut<'a, T>),
+}
+
+impl<'a, T: ?Sized> Slot<'a, T> {
+    fn get(&self) -> &T {
+        match self {
+            Slot::Row(value) => value,
+            Slot::Store(value) => value,
+        }
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        match self {
+            Slot::Row(value) => value,
+            Slot::Store(value) => value,
+        }
+    }
+}
+
+impl<'a, C: ?Sized> Mut<'a, C> {
+    pub(crate) fn new(value: &'a mut C, ticks: &'a mut ComponentTicks, tick: u64) -> Self {
+        Self {
+            value: Slot::Row(value),
+            ticks: Slot::Row(ticks),
+            tick,
+        }
+    }
+
+    pub(crate) fn stored(value: StoreRefMut<'a, C>, ticks: StoreRefMut<'a, ComponentTicks>, tick: u64) -> Self {
+        Self {
+            value: Slot::Store(value),
+            ticks: Slot::Store(ticks),
+            tick,
+        }
+    }
+}
+
+impl<'a, C: ?Sized> Deref for Mut<'a, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.value.get()
+    }
+}
+
+impl<'a, C: ?Sized> DerefMut for Mut<'a, C> {
+    fn deref_mut(&mut self) -> &mut C {
+        self.ticks.get_mut().set_changed(self.tick);
+        self.value.get_mut()
+    }
+}
+
+impl<'a, C: ?Sized + fmt::Debug> fmt::Debug for Mut<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.get().fmt(f)
+    }
+}
+
+impl<'w, T: ?Sized> StoreRef<'w, T> {
+    pub(crate) fn new(value: Ref<'w, T>, outer: Outer<'w>) -> Self {
+        Self {
+            value,
+            _outer: outer,
+        }
+    }
+
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(this: Self, f: F) -> StoreRef<'w, U> {
+        StoreRef {
+            value: Ref::map(this.value, f),
+            _outer: this._outer,
+        }
+    }
+
+    pub fn filter_map<U: ?Sized, F: FnOnce(&T) -> Option<&U>>(this: Self, f: F) -> Option<StoreRef<'w, U>> {
+        let StoreRef { value, _outer } = this;
+        Ref::filter_map(value, f).ok().map(|value| StoreRef { value, _outer })
+    }
+}
+
+impl<'w, T: ?Sized> StoreRefMut<'w, T> {
+    pub(crate) fn new(value: RefMut<'w, T>, outer: Outer<'w>) -> Self {
+        Self {
+            value,
+            _outer: outer,
+        }
+    }
+
+    pub fn map<U: ?Sized, F: FnOnce(&mut T) -> &mut U>(this: Self, f: F) -> StoreRefMut<'w, U> {
+        StoreRefMut {
+            value: RefMut::map(this.value, f),
+            _outer: this._outer,
+        }
+    }
+
+    pub fn filter_map<U: ?Sized, F: FnOnce(&mut T) -> Option<&mut U>>(this: Self, f: F) -> Option<StoreRefMut<'w, U>> {
+        let StoreRefMut { value, _outer } = this;
+        RefMut::filter_map(value, f).ok().map(|value| StoreRefMut { value, _outer })
+    }
+}
+
+impl<'w, T: ?Sized> Deref for StoreRef<'w, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'w, T: ?Sized> Deref for StoreRefMut<'w, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'w, T: ?Sized> DerefMut for StoreRefMut<'w, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<'w, T: ?Sized + fmt::Debug> fmt::Debug for StoreRef<'w, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<'w, T: ?Sized + fmt::Debug> fmt::Debug for StoreRefMut<'w, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
@@ -0,0 +1,202 @@
+use std::any::TypeId;
+use std::rc::Rc;
+
+use crate::ecs::component::Component;
+use crate::ecs::entity::Entity;
+use crate::ecs::world::WorldState;
+use crate::error::{Error, Result};
+
+/// A group of components bound together, implemented for tuples of components
+pub trait Bundle {
+    /// Bind every component, returns false if the entity does not exist
+    fn bind(self, state: &WorldState, entity: Entity) -> bool;
+}
+
+impl Bundle for () {
+    fn bind(self, state: &WorldState, entity: Entity) -> bool {
+        state.is_alive(entity)
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: 'static + Component),*> Bundle for ($($name,)*) {
+            fn bind(self, state: &WorldState, entity: Entity) -> bool {
+                let ($($name,)*) = self;
+                $(state.bind_component(entity, $name))&&*
+            }
+        }
+    }
+}
+
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
+
+/// Type erased component kept in a prefab
+trait PrefabComponent {
+    fn component_type(&self) -> TypeId;
+    fn bind_clone(&self, state: &WorldState, entity: Entity);
+    fn box_clone(&self) -> Box<dyn PrefabComponent>;
+}
+
+impl<C: 'static + Component + Clone> PrefabComponent for C {
+    fn component_type(&self) -> TypeId {
+        TypeId::of::<C>()
+    }
+
+    fn bind_clone(&self, state: &WorldState, entity: Entity) {
+        state.bind_component(entity, self.clone());
+    }
+
+    fn box_clone(&self) -> Box<dyn PrefabComponent> {
+        Box::new(self.clone())
+    }
+}
+
+/// Template of cloneable components and child templates, spawned as a fresh entity tree
+///
+/// ```ignore
+/// state.register_prefab("cube", Prefab::new().with(mesh).with(TransformComponent::default()));
+/// let cube = state.spawn_prefab("cube", (transform,))?;
+/// ```
+#[derive(Default)]
+pub struct Prefab {
+    components: Vec<Box<dyn PrefabComponent>>,
+    children: Vec<Prefab>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the component, replacing one of the same type
+    pub fn with<C: 'static + Component + Clone>(mut self, component: C) -> Self {
+        let component_type = TypeId::of::<C>();
+        self.components.retain(|existing| existing.component_type() != component_type);
+        self.components.push(Box::new(component));
+        self
+    }
+
+    /// Spawn the template as a child of every spawned root
+    pub fn with_child(mut self, child: Prefab) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// Spawn the template and its children, returning the root entity
+    pub fn spawn(&self, state: &WorldState) -> Entity {
+        let entity = state.create_entity();
+        for component in self.components.iter() {
+            component.bind_clone(state, entity);
+        }
+        for child in self.children.iter() {
+            let child = child.spawn(state);
+            // Both entities were just created, parenting can not fail
+            let _ = state.set_parent(child, entity);
+        }
+        entity
+    }
+}
+
+impl Clone for Prefab {
+    fn clone(&self) -> Self {
+        Self {
+            components: self.components.iter().map(|component| component.box_clone()).collect(),
+            children: self.children.clone(),
+        }
+    }
+}
+
+/// Bundles and prefabs
+impl WorldState {
+    /// Create an entity carrying the bundle
+    pub fn spawn<B: Bundle>(&self, bundle: B) -> Entity {
+        let entity = self.create_entity();
+        bundle.bind(self, entity);
+        entity
+    }
+
+    /// Bind every component of the bundle, returns false if the entity does not exist
+    pub fn insert_bundle<B: Bundle>(&self, entity: Entity, bundle: B) -> bool {
+        bundle.bind(self, entity)
+    }
+
+    /// Register the template under the name, returning the one it replaced
+    pub fn register_prefab(&self, name: &str, prefab: Prefab) -> Option<Prefab> {
+        self.prefabs.borrow_mut().insert(name.to_string(), Rc::new(prefab))
+            .map(|old| Rc::try_unwrap(old).unwrap_or_else(|old| (*old).clone()))
+    }
+
+    pub fn unregister_prefab(&self, name: &str) -> bool {
+        self.prefabs.borrow_mut().remove(name).is_some()
+    }
+
+    pub fn has_prefab(&self, name: &str) -> bool {
+        self.prefabs.borrow().contains_key(name)
+    }
+
+    /// Spawn the registered prefab, then bind `overrides` on its root
+    pub fn spawn_prefab<B: Bundle>(&self, name: &str, overrides: B) -> Result<Entity> {
+        let prefab = self.prefabs.borrow().get(name).cloned()
+            .ok_or_else(|| Error::UnknownPrefab(name.to_string()))?;
+        let entity = prefab.spawn(self);
+        overrides.bind(self, entity);
+        Ok(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{BasicMesh, Point3};
+    use crate::ecs::*;
+
+    fn translated(x: f32) -> TransformComponent {
+        let mut transform = TransformComponent::default();
+        transform.set_translation_xyz(x, 0., 0.);
+        transform
+    }
+
+    #[test]
+    fn test_spawn_prefab_with_overrides() {
+        let world = World::new();
+        let state = &world.state;
+        let mesh = BasicMesh::new(vec![Point3::origin(), Point3::new(1., 0., 0.)], vec![]);
+        state.register_prefab("tank", Prefab::new()
+            .with(mesh)
+            .with(translated(1.))
+            .with_child(Prefab::new().with(translated(2.)))
+        );
+
+        let first = state.spawn_prefab("tank", ()).unwrap();
+        let second = state.spawn_prefab("tank", (translated(5.), Persistent)).unwrap();
+        assert_ne!(first, second);
+        assert!(state.has_component::<MeshComponent>(second));
+        assert!(state.has_component::<Persistent>(second));
+        assert_eq!(state.get_component::<TransformComponent>(first).unwrap().translation().x, 1.);
+        assert_eq!(state.get_component::<TransformComponent>(second).unwrap().translation().x, 5.);
+
+        let turret = state.children(second)[0];
+        assert_eq!(state.get_component::<TransformComponent>(turret).unwrap().translation().x, 2.);
+        assert_eq!(state.spawn_prefab("missing", ()), Err(Error::UnknownPrefab("missing".to_string())));
+
+        let bare = state.spawn((Persistent,));
+        assert!(state.has_component::<Persistent>(bare));
+    }
+}
@@ -0,0 +1,133 @@
+use std::cell::RefCell;
+
+use crate::ecs::component::Component;
+use crate::ecs::entity::Entity;
+use crate::ecs::world::WorldState;
+
+pub type Command = Box<dyn FnOnce(&WorldState)>;
+
+/// Structural edits recorded while stores are borrowed, see `WorldState::commands`
+pub struct CommandQueue {
+    queue: RefCell<Vec<Command>>,
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        Self { queue: RefCell::new(Vec::new()) }
+    }
+
+    pub fn push(&self, command: Command) {
+        self.queue.borrow_mut().push(command);
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the recorded commands without running them
+    pub fn clear(&self) {
+        self.queue.borrow_mut().clear();
+    }
+
+    /// Run the recorded commands in order, including those they record themselves
+    pub fn apply(&self, state: &WorldState) {
+        loop {
+            let commands = std::mem::take(&mut *self.queue.borrow_mut());
+            if commands.is_empty() {
+                break;
+            }
+            for command in commands {
+                command(state);
+            }
+        }
+    }
+}
+
+impl Default for CommandQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Records spawn, despawn, bind and unbind for the next sync point
+///
+/// The world applies commands after every system, after the stage tick and on
+/// `WorldState::apply_commands`, so they are safe to record while iterating a query.
+pub struct Commands<'w> {
+    state: &'w WorldState,
+}
+
+impl<'w> Commands<'w> {
+    pub(crate) fn new(state: &'w WorldState) -> Self {
+        Self { state }
+    }
+
+    /// Reserve an entity now, it gets its components and stage owner when applied
+    pub fn spawn(&self) -> Entity {
+        let entity = self.state.entity_manager.borrow_mut().create_entity();
+        let stage = self.state.current_stage();
+        self.add(move |state| state.insert_entity(entity, stage));
+        entity
+    }
+
+    pub fn despawn(&self, entity: Entity) {
+        self.add(move |state| { state.despawn(entity); });
+    }
+
+    pub fn despawn_recursive(&self, entity: Entity) {
+        self.add(move |state| { state.despawn_recursive(entity); });
+    }
+
+    pub fn bind<C: 'static + Component>(&self, entity: Entity, component: C) {
+        self.add(move |state| { state.bind_component(entity, component); });
+    }
+
+    pub fn unbind<C: 'static + Component>(&self, entity: Entity) {
+        self.add(move |state| { state.unbind_component::<C>(entity); });
+    }
+
+    pub fn insert_resource<R: 'static>(&self, resource: R) {
+        self.add(move |state| { state.insert_resource(resource); });
+    }
+
+    /// Record an arbitrary edit
+    pub fn add<F: 'static + FnOnce(&WorldState)>(&self, command: F) {
+        self.state.command_queue.push(Box::new(command));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecs::*;
+
+    #[test]
+    fn test_commands_during_iteration() {
+        let world = World::new();
+        let state = &world.state;
+        let first = state.create_entity();
+        state.bind_component(first, TransformComponent::default());
+
+        let mut spawned = Vec::new();
+        {
+            let mut query = state.query::<&TransformComponent>().unwrap();
+            let commands = state.commands();
+            for (entity, _) in query.iter() {
+                let child = commands.spawn();
+                commands.bind(child, TransformComponent::default());
+                commands.unbind::<TransformComponent>(entity);
+                spawned.push(child);
+            }
+        }
+        assert!(state.has_component::<TransformComponent>(first));
+        state.apply_commands();
+
+        assert!(!state.has_component::<TransformComponent>(first));
+        assert!(state.has_component::<TransformComponent>(spawned[0]));
+        assert_eq!(state.get_component::<StageOwned>(spawned[0]).map(|owner| owner.0.clone()), Some("default".to_string()));
+        assert!(state.command_queue.is_empty());
+    }
+}
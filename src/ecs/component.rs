@@ -1,13 +1,164 @@
 use std::collections::HashMap;
-use std::any::{Any, TypeId};
+use std::any::{type_name, Any, TypeId};
 use std::cell::{RefCell, Ref, RefMut};
 
+use crate::error::{Error, Result};
+use crate::ecs::entity::Entity;
+use crate::ecs::storage::SparseSet;
+
 pub trait Component {
 }
 
+/// World change ticks at which a component was bound and last written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u64,
+    pub changed: u64,
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u64) -> Self {
+        Self { added: tick, changed: tick }
+    }
+
+    #[inline]
+    pub fn is_added(&self, since: u64) -> bool {
+        self.added > since
+    }
+
+    #[inline]
+    pub fn is_changed(&self, since: u64) -> bool {
+        self.changed > since
+    }
+
+    #[inline]
+    pub fn set_changed(&mut self, tick: u64) {
+        self.changed = tick;
+    }
+}
+
+/// Rows of one component type with their change ticks and recent removals
+pub struct Column<C> {
+    data: RefCell<SparseSet<C>>,
+    ticks: RefCell<SparseSet<ComponentTicks>>,
+    removed: RefCell<Vec<(Entity, u64)>>,
+}
+
+impl<C> Column<C> {
+    pub fn new() -> Self {
+        Self {
+            data: RefCell::new(SparseSet::new()),
+            ticks: RefCell::new(SparseSet::new()),
+            removed: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn data(&self) -> &RefCell<SparseSet<C>> {
+        &self.data
+    }
+
+    pub fn ticks(&self) -> &RefCell<SparseSet<ComponentTicks>> {
+        &self.ticks
+    }
+
+    fn remove(&self, entity: Entity, tick: u64) -> Option<C> {
+        let removed = self.data.borrow_mut().remove(&entity)?;
+        self.ticks.borrow_mut().remove(&entity);
+        self.removed.borrow_mut().push((entity, tick));
+        Some(removed)
+    }
+}
+
+impl<C> Default for Column<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type erased access to a single component storage
+pub trait ComponentStorage {
+    fn as_any(&self) -> &dyn Any;
+    /// Drop the component row of the entity if present, recording the removal at `tick`
+    fn remove_entity(&self, entity: Entity, tick: u64) -> bool;
+    /// Forget removals recorded at or before `tick`
+    fn prune_removed(&self, tick: u64);
+    fn contains(&self, entity: Entity) -> bool;
+    /// Mutably borrow the rows, ticks and removals together, used by `WorldState::restore`
+    fn try_lock(&self) -> Result<Box<dyn ColumnLock + '_>>;
+}
+
+/// Exclusive borrow of a whole column, so writes through it cannot conflict
+pub trait ColumnLock {
+    fn contains(&self, entity: Entity) -> bool;
+    /// Drop the rows of entities failing `keep` without recording removals
+    fn retain(&mut self, keep: &dyn Fn(Entity) -> bool);
+    fn clear_removed(&mut self);
+    /// Swap in a `(SparseSet<C>, SparseSet<ComponentTicks>)` pair, other types are ignored
+    fn replace(&mut self, copy: Box<dyn Any>);
+}
+
+struct ColumnGuard<'a, C> {
+    data: RefMut<'a, SparseSet<C>>,
+    ticks: RefMut<'a, SparseSet<ComponentTicks>>,
+    removed: RefMut<'a, Vec<(Entity, u64)>>,
+}
+
+impl<'a, C: 'static> ColumnLock for ColumnGuard<'a, C> {
+    fn contains(&self, entity: Entity) -> bool {
+        self.data.contains_key(&entity)
+    }
+
+    fn retain(&mut self, keep: &dyn Fn(Entity) -> bool) {
+        let dropped: Vec<Entity> = self.data.keys().copied().filter(|entity| !keep(*entity)).collect();
+        for entity in dropped {
+            self.data.remove(&entity);
+            self.ticks.remove(&entity);
+        }
+    }
+
+    fn clear_removed(&mut self) {
+        self.removed.clear();
+    }
+
+    fn replace(&mut self, copy: Box<dyn Any>) {
+        if let Ok(copy) = copy.downcast::<(SparseSet<C>, SparseSet<ComponentTicks>)>() {
+            let (data, ticks) = *copy;
+            *self.data = data;
+            *self.ticks = ticks;
+        }
+    }
+}
+
+impl<C: 'static + Component> ComponentStorage for Column<C> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn remove_entity(&self, entity: Entity, tick: u64) -> bool {
+        self.remove(entity, tick).is_some()
+    }
+
+    fn prune_removed(&self, tick: u64) {
+        self.removed.borrow_mut().retain(|(_, removed)| *removed > tick);
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.data.borrow().contains_key(&entity)
+    }
+
+    fn try_lock(&self) -> Result<Box<dyn ColumnLock + '_>> {
+        let conflict = |_| Error::BorrowConflict(type_name::<C>());
+        Ok(Box::new(ColumnGuard {
+            data: self.data.try_borrow_mut().map_err(conflict)?,
+            ticks: self.ticks.try_borrow_mut().map_err(conflict)?,
+            removed: self.removed.try_borrow_mut().map_err(conflict)?,
+        }))
+    }
+}
+
 pub type ComponentStore = RefCell<ComponentStoreProto>;
 pub struct ComponentStoreProto {
-    store: HashMap<TypeId, Box<dyn Any>>
+    store: HashMap<TypeId, Box<dyn ComponentStorage>>
 }
 
 
@@ -17,45 +168,145 @@ impl ComponentStoreProto {
     }
 
     pub fn register<C: 'static + Component>(&mut self) {
-        self.store.entry(TypeId::of::<C>()).or_insert(Box::new(RefCell::new(HashMap::new() as HashMap<u32, C>)));
+        self.store.entry(TypeId::of::<C>()).or_insert_with(|| Box::new(Column::<C>::new()));
+    }
+
+    pub fn column<C: 'static + Component>(&self) -> Option<&Column<C>> {
+        self.store.get(&TypeId::of::<C>()).and_then(|storage| storage.as_any().downcast_ref::<Column<C>>())
     }
 
-    pub fn get<C: 'static + Component>(&self) -> Ref<HashMap<u32, C>> {
-        self.store.get(&TypeId::of::<C>()).unwrap().downcast_ref::<RefCell<HashMap<u32, C>>>().unwrap().borrow()
+    /// Storage cell of a registered component type
+    pub fn storage<C: 'static + Component>(&self) -> Option<&RefCell<SparseSet<C>>> {
+        self.column::<C>().map(Column::data)
     }
-    pub fn get_mut<C: 'static + Component>(&self) -> RefMut<HashMap<u32, C>> {
-        self.store.get(&TypeId::of::<C>()).unwrap().downcast_ref::<RefCell<HashMap<u32, C>>>().unwrap().borrow_mut()
+
+    pub fn try_ticks<C: 'static + Component>(&self) -> Result<Ref<'_, SparseSet<ComponentTicks>>> {
+        self.column::<C>().ok_or(Error::UnknownComponent(type_name::<C>()))?
+            .ticks().try_borrow().map_err(|_| Error::BorrowConflict(type_name::<C>()))
+    }
+
+    pub fn try_ticks_mut<C: 'static + Component>(&self) -> Result<RefMut<'_, SparseSet<ComponentTicks>>> {
+        self.column::<C>().ok_or(Error::UnknownComponent(type_name::<C>()))?
+            .ticks().try_borrow_mut().map_err(|_| Error::BorrowConflict(type_name::<C>()))
+    }
+
+    /// Insert or replace the component, a replaced component counts as changed rather than added
+    ///
+    /// Panics if the component type is not registered or the storage is borrowed, see `try_insert`.
+    pub fn insert<C: 'static + Component>(&self, entity: Entity, component: C, tick: u64) -> Option<C> {
+        self.try_insert(entity, component, tick).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Both the data and the ticks are borrowed before anything is written
+    pub fn try_insert<C: 'static + Component>(&self, entity: Entity, component: C, tick: u64) -> Result<Option<C>> {
+        let mut data = self.try_get_mut::<C>()?;
+        let mut ticks = self.try_ticks_mut::<C>()?;
+        let replaced = data.insert(entity, component);
+        match ticks.get_mut(&entity) {
+            Some(current) if replaced.is_some() => current.set_changed(tick),
+            _ => { ticks.insert(entity, ComponentTicks::new(tick)); }
+        }
+        Ok(replaced)
+    }
+
+    /// Remove the component, recording the removal at `tick`
+    pub fn remove<C: 'static + Component>(&self, entity: Entity, tick: u64) -> Option<C> {
+        self.column::<C>()?.remove(entity, tick)
     }
 
-    pub fn borrow(&self) -> &HashMap<TypeId, Box<dyn Any>> {
+    pub fn mark_changed<C: 'static + Component>(&self, entity: Entity, tick: u64) {
+        if let Some(ticks) = self.try_ticks_mut::<C>().ok().as_mut().and_then(|ticks| ticks.get_mut(&entity)) {
+            ticks.set_changed(tick);
+        }
+    }
+
+    /// Entities that lost the component after `since`, oldest first
+    pub fn removed<C: 'static + Component>(&self, since: u64) -> Vec<Entity> {
+        match self.column::<C>() {
+            Some(column) => column.removed.borrow().iter()
+                .filter(|(_, tick)| *tick > since)
+                .map(|(entity, _)| *entity)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn try_get<C: 'static + Component>(&self) -> Result<Ref<'_, SparseSet<C>>> {
+        self.storage::<C>().ok_or(Error::UnknownComponent(type_name::<C>()))?
+            .try_borrow().map_err(|_| Error::BorrowConflict(type_name::<C>()))
+    }
+
+    pub fn try_get_mut<C: 'static + Component>(&self) -> Result<RefMut<'_, SparseSet<C>>> {
+        self.storage::<C>().ok_or(Error::UnknownComponent(type_name::<C>()))?
+            .try_borrow_mut().map_err(|_| Error::BorrowConflict(type_name::<C>()))
+    }
+
+    /// Panics if the component type is not registered or the storage is mutably borrowed, see `try_get`
+    pub fn get<C: 'static + Component>(&self) -> Ref<'_, SparseSet<C>> {
+        self.try_get::<C>().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Panics if the component type is not registered or the storage is borrowed, see `try_get_mut`
+    pub fn get_mut<C: 'static + Component>(&self) -> RefMut<'_, SparseSet<C>> {
+        self.try_get_mut::<C>().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Remove the entity from every registered component storage
+    pub fn remove_entity(&self, entity: Entity, tick: u64) {
+        for storage in self.store.values() {
+            storage.remove_entity(entity, tick);
+        }
+    }
+
+    /// Forget removals recorded at or before `tick`
+    pub fn prune_removed(&self, tick: u64) {
+        for storage in self.store.values() {
+            storage.prune_removed(tick);
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn borrow(&self) -> &HashMap<TypeId, Box<dyn ComponentStorage>> {
         &self.store
     }
 }
 
+/// Hands out sequential component ids, used as bit positions of an entity `Signature`
 pub struct ComponentManager {
-    coder: u32,
-    store: HashMap<TypeId, u32>,
+    coder: usize,
+    store: HashMap<TypeId, usize>,
 }
 
 impl ComponentManager {
     pub fn new() -> Self {
         Self {
-            coder: 1,
+            coder: 0,
             store: HashMap::new(),
         }
     }
-    pub fn register_component<C: 'static + Component>(&mut self) -> u32 {
+    pub fn register_component<C: 'static + Component>(&mut self) -> usize {
         let type_id = TypeId::of::<C>();
         if let Some(id) = self.store.get(&type_id) {
-            return id.clone();
+            return *id;
         }
         let id = self.coder;
-        self.coder *= 2;
+        self.coder += 1;
         self.store.insert(type_id, id);
         id
     }
 
-    pub fn get_code<C: 'static + Component>(&self) -> Option<&u32> {
+    pub fn get_code<C: 'static + Component>(&self) -> Option<&usize> {
         self.store.get(&TypeId::of::<C>())
     }
+
+    /// Id of a type erased component type
+    pub fn id_of(&self, type_id: &TypeId) -> Option<usize> {
+        self.store.get(type_id).copied()
+    }
+}
+
+impl Default for ComponentManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
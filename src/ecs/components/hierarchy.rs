@@ -0,0 +1,12 @@
+use crate::ecs::Component;
+use crate::ecs::entity::Entity;
+
+/// Parent of an entity in the scene hierarchy, maintained by `WorldState::set_parent`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+impl Component for Parent {}
+
+/// Direct children of an entity in the scene hierarchy, maintained by `WorldState::set_parent`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Children(pub Vec<Entity>);
+impl Component for Children {}
@@ -1,10 +1,16 @@
 pub mod mesh;
 pub mod camera;
 pub mod transform;
+pub mod hierarchy;
+pub mod stage;
+pub mod name;
 
 pub use camera::CameraComponent;
 pub use mesh::MeshComponent;
-pub use transform::TransformComponent;
+pub use transform::{TransformComponent, GlobalTransform};
+pub use hierarchy::{Parent, Children};
+pub use stage::{StageOwned, Persistent};
+pub use name::{Name, Tags};
 
 use crate::ecs::Component;
 use crate::core::Shape;
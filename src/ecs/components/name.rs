@@ -0,0 +1,21 @@
+use std::collections::BTreeSet;
+
+use crate::ecs::Component;
+
+/// Human readable entity name, maintained by `WorldState::set_name`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Name(pub String);
+impl Component for Name {}
+
+impl Name {
+    pub fn new(name: &str) -> Self {
+        Name(name.to_string())
+    }
+}
+
+/// Free form labels of an entity, maintained by `WorldState::add_tag`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tags(pub BTreeSet<String>);
+impl Component for Tags {}
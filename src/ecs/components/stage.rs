@@ -0,0 +1,12 @@
+use crate::ecs::Component;
+
+/// Name of the stage that was active when the entity was created, set by `WorldState::create_entity`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StageOwned(pub String);
+impl Component for StageOwned {}
+
+/// Keeps the entity alive when its owning stage cleans up on exit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Persistent;
+impl Component for Persistent {}
@@ -1,9 +1,40 @@
 use crate::core::transform::Transform3;
+use crate::core::{Matrix4, Point3, Vector3};
 use crate::ecs::component::Component;
 
 pub type TransformComponent = Transform3;
 
 impl Component for TransformComponent {}
 
+/// World space matrix of an entity, its local `Transform3` composed with all of its ancestors
+///
+/// Written by `TransformSystem`, do not edit by hand.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlobalTransform(pub Matrix4<f32>);
 
+impl GlobalTransform {
+    pub fn identity() -> Self {
+        GlobalTransform(Matrix4::identity())
+    }
 
+    #[inline]
+    pub fn matrix(&self) -> &Matrix4<f32> {
+        &self.0
+    }
+
+    pub fn translation(&self) -> Vector3<f32> {
+        Vector3::new(self.0[(0, 3)], self.0[(1, 3)], self.0[(2, 3)])
+    }
+
+    pub fn transform_point(&self, point: &Point3<f32>) -> Point3<f32> {
+        self.0.transform_point(point)
+    }
+}
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Component for GlobalTransform {}
@@ -1,31 +1,134 @@
+use std::fmt;
 
+use crate::ecs::signature::Signature;
+
+/// Generational entity handle
+///
+/// The index slot is recycled after an entity is despawned, the generation
+/// is bumped on every reuse so stale handles never alias a new entity.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Entity {
-    pub id: u32,
-    pub components: u32,
+    index: u32,
+    generation: u32,
 }
 
 impl Entity {
+    pub fn new(index: u32, generation: u32) -> Self {
+        Self {
+            index,
+            generation,
+        }
+    }
+
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Entity({}v{})", self.index, self.generation)
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
 }
 
+/// Per entity record kept in the world's entity store
+#[derive(Clone)]
+pub struct EntityEntry {
+    pub entity: Entity,
+    pub components: Signature,
+}
+
+impl EntityEntry {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            components: Signature::new(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct EntityManager {
-    coder: u32,
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
 }
 
 impl EntityManager {
     pub fn new() -> Self {
-        Self {
-            coder: 1,
-        }
+        Self::default()
     }
 
     pub fn create_entity(&mut self) -> Entity {
-        let id = self.coder;
-        self.coder += 1;
-        Entity {
-            id,
-            components: 0,
+        if let Some(index) = self.free.pop() {
+            let slot = index as usize;
+            self.alive[slot] = true;
+            return Entity::new(index, self.generations[slot]);
         }
+        let index = self.generations.len() as u32;
+        self.generations.push(0);
+        self.alive.push(true);
+        Entity::new(index, 0)
+    }
+
+    /// Release the slot of a live entity, returns false for stale or unknown handles
+    pub fn destroy_entity(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let slot = entity.index as usize;
+        self.alive[slot] = false;
+        self.generations[slot] = self.generations[slot].wrapping_add(1);
+        self.free.push(entity.index);
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let slot = entity.index as usize;
+        slot < self.alive.len() && self.alive[slot] && self.generations[slot] == entity.generation
+    }
+
+    /// Number of live entities
+    pub fn len(&self) -> usize {
+        self.alive.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recycle_bumps_generation() {
+        let mut manager = EntityManager::new();
+        let a = manager.create_entity();
+        let b = manager.create_entity();
+        assert_ne!(a, b);
+        assert!(manager.destroy_entity(a));
+        assert!(!manager.destroy_entity(a));
+        assert!(!manager.is_alive(a));
 
+        let c = manager.create_entity();
+        assert_eq!(c.index(), a.index());
+        assert_eq!(c.generation(), a.generation() + 1);
+        assert!(manager.is_alive(c));
+        assert!(!manager.is_alive(a));
+        assert_eq!(manager.len(), 2);
+    }
+}
@@ -0,0 +1,144 @@
+use std::marker::PhantomData;
+
+/// Double buffered queue of events of one type, stored as a world resource
+///
+/// Events stay readable for the tick they were sent in and the following one,
+/// `WorldState::tick` rotates the buffers once per tick.
+pub struct Events<E> {
+    previous: Vec<E>,
+    previous_start: usize,
+    current: Vec<E>,
+    current_start: usize,
+}
+
+impl<E> Events<E> {
+    pub fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            previous_start: 0,
+            current: Vec::new(),
+            current_start: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+    }
+
+    /// Drop the events of the older buffer and start a new one
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+        self.previous_start = self.current_start;
+        self.current_start += self.previous.len();
+    }
+
+    /// Number of events sent since the queue was created
+    pub fn count(&self) -> usize {
+        self.current_start + self.current.len()
+    }
+
+    /// Number of events still buffered
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.update();
+        self.update();
+    }
+
+    /// Iterate over every buffered event, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    /// Buffered events sent at or after the event id `from`
+    fn since(&self, from: usize) -> impl Iterator<Item = &E> {
+        let previous = from.saturating_sub(self.previous_start).min(self.previous.len());
+        let current = from.saturating_sub(self.current_start).min(self.current.len());
+        self.previous[previous..].iter().chain(self.current[current..].iter())
+    }
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per reader cursor into an `Events<E>` queue
+///
+/// Every reader sees each event at most once, events rotated out
+/// before the reader caught up are skipped.
+pub struct EventReader<E> {
+    last: usize,
+    _event: PhantomData<fn() -> E>,
+}
+
+impl<E> EventReader<E> {
+    pub fn new() -> Self {
+        Self {
+            last: 0,
+            _event: PhantomData,
+        }
+    }
+
+    /// Events sent since the last read
+    pub fn read<'a>(&mut self, events: &'a Events<E>) -> impl Iterator<Item = &'a E> {
+        let from = self.last;
+        self.last = events.count();
+        events.since(from)
+    }
+
+    /// Skip everything sent so far
+    pub fn clear(&mut self, events: &Events<E>) {
+        self.last = events.count();
+    }
+
+    /// Number of unread events still buffered
+    pub fn len(&self, events: &Events<E>) -> usize {
+        events.since(self.last).count()
+    }
+
+    pub fn is_empty(&self, events: &Events<E>) -> bool {
+        self.len(events) == 0
+    }
+}
+
+impl<E> Default for EventReader<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readers_and_rotation() {
+        let mut events = Events::new();
+        let mut early = EventReader::new();
+        let mut late = EventReader::new();
+
+        events.send(1);
+        events.send(2);
+        assert_eq!(early.read(&events).cloned().collect::<Vec<_>>(), vec![1, 2]);
+        events.update();
+        events.send(3);
+        assert_eq!(early.read(&events).cloned().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(late.len(&events), 3);
+
+        events.update();
+        events.update();
+        events.send(4);
+        // The first two frames were rotated out before the late reader caught up
+        assert_eq!(late.read(&events).cloned().collect::<Vec<_>>(), vec![4]);
+        assert!(late.is_empty(&events));
+        assert_eq!(events.count(), 4);
+    }
+}
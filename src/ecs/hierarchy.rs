@@ -0,0 +1,259 @@
+use crate::core::Matrix4;
+use crate::ecs::entity::Entity;
+use crate::ecs::components::*;
+use crate::ecs::world::WorldState;
+use crate::error::{Error, Result};
+
+/// Scene hierarchy helpers, keeping `Parent` and `Children` in sync
+impl WorldState {
+    /// Attach `child` under `parent`, detaching it from its previous parent
+    pub fn set_parent(&self, child: Entity, parent: Entity) -> Result<()> {
+        for entity in [child, parent].iter() {
+            if !self.is_alive(*entity) {
+                return Err(Error::NoSuchEntity(*entity));
+            }
+        }
+        // Walk up from the new parent, meeting the child means a loop
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            if entity == child {
+                return Err(Error::HierarchyCycle(child));
+            }
+            ancestor = self.parent(entity);
+        }
+
+        self.detach_from_parent(child);
+        self.bind_component(child, Parent(parent));
+        let appended = match self.get_component_mut::<Children>(parent) {
+            Some(mut children) => {
+                children.0.push(child);
+                true
+            }
+            None => false,
+        };
+        if !appended {
+            self.bind_component(parent, Children(vec![child]));
+        }
+        Ok(())
+    }
+
+    /// Detach `child` from its parent, making it a root
+    pub fn remove_parent(&self, child: Entity) -> Result<()> {
+        if !self.is_alive(child) {
+            return Err(Error::NoSuchEntity(child));
+        }
+        self.detach_from_parent(child);
+        Ok(())
+    }
+
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.get_component::<Parent>(entity).map(|parent| parent.0)
+    }
+
+    pub fn children(&self, entity: Entity) -> Vec<Entity> {
+        self.get_component::<Children>(entity).map(|children| children.0.clone()).unwrap_or_default()
+    }
+
+    /// All entities below `entity`, depth first
+    pub fn descendants(&self, entity: Entity) -> Vec<Entity> {
+        let mut descendants = Vec::new();
+        let mut stack = self.children(entity);
+        stack.reverse();
+        while let Some(next) = stack.pop() {
+            descendants.push(next);
+            let mut children = self.children(next);
+            children.reverse();
+            stack.extend(children);
+        }
+        descendants
+    }
+
+    /// Despawn the entity together with all of its descendants
+    pub fn despawn_recursive(&self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let descendants = self.descendants(entity);
+        self.despawn(entity);
+        for descendant in descendants {
+            self.despawn(descendant);
+        }
+        true
+    }
+
+    /// Recompute `GlobalTransform` of every entity with a `TransformComponent`
+    ///
+    /// Entities without a transform in the middle of the tree pass their parent's matrix through.
+    /// Entities that lost their transform also lose their `GlobalTransform`.
+    pub fn propagate_transforms(&self) {
+        let stale: Vec<Entity> = self.components::<GlobalTransform>()
+            .map(|stored| stored.keys().filter(|entity| !self.has_component::<TransformComponent>(**entity)).copied().collect())
+            .unwrap_or_default();
+        for entity in stale {
+            self.unbind_component::<GlobalTransform>(entity);
+        }
+
+        let mut globals: Vec<(Entity, Matrix4<f32>)> = Vec::new();
+        {
+            let transforms = match self.components::<TransformComponent>() {
+                Some(transforms) => transforms,
+                None => return,
+            };
+            let children = self.components::<Children>();
+            let parents = self.components::<Parent>();
+            let is_root = |entity: &Entity| parents.as_ref().map_or(true, |parents| !parents.contains_key(entity));
+
+            let mut stack: Vec<(Entity, Matrix4<f32>)> = transforms.keys()
+                .chain(children.iter().flat_map(|children| children.keys()))
+                .filter(|entity| is_root(entity))
+                .map(|entity| (*entity, Matrix4::identity()))
+                .collect();
+            stack.sort_by_key(|(entity, _)| *entity);
+            stack.dedup_by_key(|(entity, _)| *entity);
+
+            while let Some((entity, parent)) = stack.pop() {
+                let global = match transforms.get(&entity) {
+                    Some(transform) => {
+                        let global = parent * transform.matrix();
+                        globals.push((entity, global));
+                        global
+                    }
+                    None => parent,
+                };
+                if let Some(children) = children.as_ref().and_then(|children| children.get(&entity)) {
+                    stack.extend(children.0.iter().map(|child| (*child, global)));
+                }
+            }
+        }
+
+        let mut missing = Vec::new();
+        let mut changed = Vec::new();
+        {
+            let mut stored = self.components_mut::<GlobalTransform>();
+            for (entity, global) in globals {
+                match stored.as_mut().and_then(|stored| stored.get_mut(&entity)) {
+                    // Only touch moved entities so `Changed<GlobalTransform>` stays meaningful
+                    Some(current) if current.0 != global => {
+                        current.0 = global;
+                        changed.push(entity);
+                    }
+                    Some(_) => {}
+                    None => missing.push((entity, global)),
+                }
+            }
+        }
+        for entity in changed {
+            self.mark_changed::<GlobalTransform>(entity);
+        }
+        for (entity, global) in missing {
+            self.bind_component(entity, GlobalTransform(global));
+        }
+    }
+
+    /// Matrix placing the entity in the world
+    ///
+    /// Prefers the propagated `GlobalTransform`, falls back to the local transform and
+    /// to identity for entities without either.
+    pub fn world_matrix(&self, entity: Entity) -> Matrix4<f32> {
+        if let Some(global) = self.get_component::<GlobalTransform>(entity) {
+            return *global.matrix();
+        }
+        self.get_component::<TransformComponent>(entity)
+            .map(|transform| transform.matrix())
+            .unwrap_or_else(Matrix4::identity)
+    }
+
+    /// Unlink the entity from its parent and orphan its children, used before despawning
+    pub(crate) fn detach_hierarchy(&self, entity: Entity) {
+        self.detach_from_parent(entity);
+        if let Some(children) = self.take_component::<Children>(entity) {
+            for child in children.0 {
+                self.unbind_component::<Parent>(child);
+            }
+        }
+    }
+
+    fn detach_from_parent(&self, child: Entity) {
+        if let Some(Parent(parent)) = self.take_component::<Parent>(child) {
+            let empty = match self.get_component_mut::<Children>(parent) {
+                Some(mut children) => {
+                    children.0.retain(|entity| *entity != child);
+                    children.0.is_empty()
+                }
+                None => false,
+            };
+            if empty {
+                self.unbind_component::<Children>(parent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecs::*;
+    use crate::error::Error;
+
+    fn translated(x: f32, y: f32, z: f32) -> TransformComponent {
+        let mut transform = TransformComponent::default();
+        transform.set_translation_xyz(x, y, z);
+        transform
+    }
+
+    #[test]
+    fn test_propagate_and_reparent() {
+        let world = World::new();
+        let state = &world.state;
+        let tank = state.create_entity();
+        let turret = state.create_entity();
+        let barrel = state.create_entity();
+        state.bind_component(tank, translated(10., 0., 0.));
+        state.bind_component(turret, translated(0., 5., 0.));
+        state.bind_component(barrel, translated(0., 0., 2.));
+        state.set_parent(turret, tank).unwrap();
+        state.set_parent(barrel, turret).unwrap();
+        assert_eq!(state.set_parent(tank, barrel), Err(Error::HierarchyCycle(tank)));
+
+        state.propagate_transforms();
+        let global = *state.get_component::<GlobalTransform>(barrel).unwrap();
+        assert_eq!(global.translation(), crate::core::Vector3::new(10., 5., 2.));
+
+        state.remove_parent(turret).unwrap();
+        assert!(state.children(tank).is_empty());
+        assert!(!state.has_component::<Children>(tank));
+        state.propagate_transforms();
+        let global = *state.get_component::<GlobalTransform>(barrel).unwrap();
+        assert_eq!(global.translation(), crate::core::Vector3::new(0., 5., 2.));
+
+        state.set_parent(turret, tank).unwrap();
+        assert_eq!(state.descendants(tank), vec![turret, barrel]);
+        assert!(state.despawn_recursive(tank));
+        assert!(!state.is_alive(barrel));
+    }
+
+    #[test]
+    fn test_removed_transform_drops_global() {
+        let world = World::new();
+        let state = &world.state;
+        let prop = state.spawn((translated(1., 2., 3.),));
+        state.propagate_transforms();
+        assert!(state.has_component::<GlobalTransform>(prop));
+
+        state.unbind_component::<TransformComponent>(prop);
+        state.propagate_transforms();
+        assert!(!state.has_component::<GlobalTransform>(prop));
+        assert_eq!(state.world_matrix(prop), crate::core::Matrix4::identity());
+    }
+
+    #[test]
+    fn test_despawn_orphans_children() {
+        let world = World::new();
+        let state = &world.state;
+        let parent = state.create_entity();
+        let child = state.create_entity();
+        state.set_parent(child, parent).unwrap();
+        state.despawn(parent);
+        assert!(state.is_alive(child));
+        assert_eq!(state.parent(child), None);
+    }
+}
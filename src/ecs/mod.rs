@@ -1,24 +1,61 @@
 
 pub mod entity;
+pub mod signature;
+pub mod storage;
+pub mod borrow;
 pub mod component;
 pub mod system;
 pub mod world;
+pub mod query;
+pub mod commands;
+pub mod bundle;
+pub mod snapshot;
+#[cfg(feature = "serialize")]
+pub mod scene;
+pub mod time;
+pub mod resource;
+pub mod event;
+pub mod hierarchy;
+pub mod name;
 pub mod components;
 pub mod systems;
 pub mod stage;
 
+pub use entity::Entity;
+pub use signature::Signature;
+pub use storage::SparseSet;
+pub use borrow::{Mut, StoreRef, StoreRefMut};
 pub use component::Component;
 pub use components::{
     CameraComponent,
     MeshComponent,
     TransformComponent,
     WidgetComponent,
+    GlobalTransform,
+    Parent,
+    Children,
+    StageOwned,
+    Persistent,
+    Name,
+    Tags,
 };
 
-pub use system::System;
+pub use query::{Query, With, Without, Added, Changed};
+
+pub use system::{System, SystemContext, SystemDescriptor, SystemStage};
+pub use time::Time;
+pub use event::{Events, EventReader};
+pub use commands::Commands;
+pub use bundle::{Bundle, Prefab};
+pub use snapshot::{Snapshot, InputLog};
+#[cfg(feature = "serialize")]
+pub use scene::{Scene, SceneEntity, SceneRegistry};
 pub use systems::rendering::RenderingSystem;
+pub use systems::raster::RasterSystem;
+pub use systems::svg::{Svg, SvgSystem};
+pub use systems::transform::TransformSystem;
 
-pub use stage::Stage;
+pub use stage::{Stage, Trans};
 
 pub use world::{ World, WorldState };
 
@@ -0,0 +1,311 @@
+use std::any::{Any, TypeId};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ecs::component::Component;
+use crate::ecs::entity::Entity;
+use crate::ecs::components::*;
+use crate::ecs::world::WorldState;
+use crate::error::{Error, Result};
+
+/// Lookup from names and tags to entities, kept next to the `Name` and `Tags` components
+#[derive(Default)]
+pub struct NameIndex {
+    names: HashMap<String, Vec<Entity>>,
+    tags: HashMap<String, BTreeSet<Entity>>,
+    /// Change tick of the last sync, rows changed since are re-indexed by the next one
+    synced: u64,
+    /// A whole `Name` or `Tags` storage was borrowed mutably, the next sync rebuilds everything
+    stale: bool,
+}
+
+impl NameIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    fn unname(&mut self, name: &str, entity: Entity) {
+        if let Some(entities) = self.names.get_mut(name) {
+            entities.retain(|named| *named != entity);
+            if entities.is_empty() {
+                self.names.remove(name);
+            }
+        }
+    }
+
+    fn untag(&mut self, tag: &str, entity: Entity) {
+        if let Some(entities) = self.tags.get_mut(tag) {
+            entities.remove(&entity);
+            if entities.is_empty() {
+                self.tags.remove(tag);
+            }
+        }
+    }
+}
+
+/// Whether binding or removing `C` has to update the `NameIndex`
+pub(crate) fn is_indexed<C: 'static>() -> bool {
+    TypeId::of::<C>() == TypeId::of::<Name>() || TypeId::of::<C>() == TypeId::of::<Tags>()
+}
+
+/// Entity names and tags, the index follows every bind and removal of `Name` and `Tags`
+/// and picks up in-place edits at the next `apply_commands`
+impl WorldState {
+    /// Name the entity, replacing its previous name
+    pub fn set_name(&self, entity: Entity, name: &str) -> Result<()> {
+        if !self.is_alive(entity) {
+            return Err(Error::NoSuchEntity(entity));
+        }
+        self.bind_component(entity, Name(name.to_string()));
+        Ok(())
+    }
+
+    pub fn name(&self, entity: Entity) -> Option<String> {
+        self.get_component::<Name>(entity).map(|name| name.0.clone())
+    }
+
+    /// First entity given the name that still carries it
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.find_all_by_name(name).into_iter().next()
+    }
+
+    /// Entities sharing the name, in naming order
+    pub fn find_all_by_name(&self, name: &str) -> Vec<Entity> {
+        let named = self.name_index.borrow().names.get(name).cloned().unwrap_or_default();
+        named.into_iter().filter(|entity| self.name(*entity).as_deref() == Some(name)).collect()
+    }
+
+    pub fn add_tag(&self, entity: Entity, tag: &str) -> Result<()> {
+        if !self.is_alive(entity) {
+            return Err(Error::NoSuchEntity(entity));
+        }
+        let tagged = match self.get_component_mut::<Tags>(entity) {
+            Some(mut tags) => {
+                tags.0.insert(tag.to_string());
+                true
+            }
+            None => false,
+        };
+        if !tagged {
+            self.bind_component(entity, Tags(std::iter::once(tag.to_string()).collect()));
+        }
+        self.name_index.borrow_mut().tags.entry(tag.to_string()).or_default().insert(entity);
+        Ok(())
+    }
+
+    /// Remove the tag, returns false if the entity did not carry it
+    pub fn remove_tag(&self, entity: Entity, tag: &str) -> bool {
+        let removed = match self.get_component_mut::<Tags>(entity) {
+            Some(mut tags) => tags.0.remove(tag),
+            None => false,
+        };
+        if removed {
+            self.name_index.borrow_mut().untag(tag, entity);
+        }
+        removed
+    }
+
+    pub fn has_tag(&self, entity: Entity, tag: &str) -> bool {
+        self.get_component::<Tags>(entity).is_some_and(|tags| tags.0.contains(tag))
+    }
+
+    /// Entities carrying the tag, sorted by handle
+    pub fn entities_with_tag(&self, tag: &str) -> Vec<Entity> {
+        let tagged = self.name_index.borrow().tags.get(tag).cloned().unwrap_or_default();
+        tagged.into_iter().filter(|entity| self.has_tag(*entity, tag)).collect()
+    }
+
+    /// Add the entity's current `Name` and `Tags` to the index
+    pub fn index_entity(&self, entity: Entity) {
+        let name = self.name(entity);
+        let tags = self.get_component::<Tags>(entity).map(|tags| tags.0.clone()).unwrap_or_default();
+        let mut index = self.name_index.borrow_mut();
+        if let Some(name) = name {
+            let named = index.names.entry(name).or_default();
+            if !named.contains(&entity) {
+                named.push(entity);
+            }
+        }
+        for tag in tags {
+            index.tags.entry(tag).or_default().insert(entity);
+        }
+    }
+
+    /// Swap the index entries of a replaced or removed `Name` or `Tags` for the current ones
+    pub(crate) fn reindex_entity(&self, entity: Entity, old: Option<&dyn Any>) {
+        {
+            let mut index = self.name_index.borrow_mut();
+            if let Some(Name(name)) = old.and_then(|old| old.downcast_ref::<Name>()) {
+                index.unname(name, entity);
+            }
+            if let Some(Tags(tags)) = old.and_then(|old| old.downcast_ref::<Tags>()) {
+                for tag in tags {
+                    index.untag(tag, entity);
+                }
+            }
+        }
+        self.index_entity(entity);
+    }
+
+    /// Pick up `Name` and `Tags` written through `get_component_mut`, `&mut` queries or
+    /// `components_mut`, called by `apply_commands`
+    pub(crate) fn sync_name_index(&self) {
+        let now = self.change_tick();
+        let (since, stale) = {
+            let index = self.name_index.borrow();
+            (index.synced, index.stale)
+        };
+        let changed = if stale {
+            self.entity_store.borrow().keys().copied().collect()
+        } else {
+            match (self.changed_since::<Name>(since), self.changed_since::<Tags>(since)) {
+                (Some(mut changed), Some(tags)) => {
+                    changed.extend(tags);
+                    changed
+                }
+                // Rows still borrowed, the next sync picks them up
+                _ => return,
+            }
+        };
+        {
+            let mut index = self.name_index.borrow_mut();
+            if stale {
+                *index = NameIndex::default();
+            }
+            index.synced = now;
+        }
+        for entity in changed {
+            self.refresh_entity(entity);
+        }
+    }
+
+    /// Entities whose `C` changed at or after `since`, `None` while its ticks are borrowed
+    fn changed_since<C: 'static + Component>(&self, since: u64) -> Option<Vec<Entity>> {
+        let store = self.component_store.borrow();
+        let changed = match store.try_ticks::<C>() {
+            Ok(ticks) => Some(ticks.iter().filter(|(_, ticks)| ticks.changed >= since).map(|(entity, _)| *entity).collect()),
+            Err(Error::UnknownComponent(_)) => Some(Vec::new()),
+            Err(_) => None,
+        };
+        changed
+    }
+
+    /// Drop the entries the entity no longer carries, then add its current ones
+    fn refresh_entity(&self, entity: Entity) {
+        let name = self.name(entity);
+        let tags = self.get_component::<Tags>(entity).map(|tags| tags.0.clone()).unwrap_or_default();
+        {
+            let mut index = self.name_index.borrow_mut();
+            let names: Vec<String> = index.names.iter()
+                .filter(|(named, entities)| Some(*named) != name.as_ref() && entities.contains(&entity))
+                .map(|(named, _)| named.clone())
+                .collect();
+            for named in names {
+                index.unname(&named, entity);
+            }
+            let untagged: Vec<String> = index.tags.iter()
+                .filter(|(tag, entities)| !tags.contains(*tag) && entities.contains(&entity))
+                .map(|(tag, _)| tag.clone())
+                .collect();
+            for tag in untagged {
+                index.untag(&tag, entity);
+            }
+        }
+        self.index_entity(entity);
+    }
+
+    /// Drop the entity from the name and tag index, used before despawning
+    pub(crate) fn unindex_entity(&self, entity: Entity) {
+        let name = self.name(entity);
+        let tags = self.get_component::<Tags>(entity).map(|tags| tags.0.clone()).unwrap_or_default();
+        let mut index = self.name_index.borrow_mut();
+        if let Some(name) = name {
+            index.unname(&name, entity);
+        }
+        for tag in tags {
+            index.untag(&tag, entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ecs::*;
+
+    #[test]
+    fn test_names_and_tags() {
+        let world = World::new();
+        let state = &world.state;
+        let player = state.create_entity();
+        let grunt = state.create_entity();
+        let boss = state.create_entity();
+        state.set_name(player, "hero").unwrap();
+        state.set_name(player, "player").unwrap();
+        state.set_name(boss, "boss").unwrap();
+        state.add_tag(grunt, "enemy").unwrap();
+        state.add_tag(boss, "enemy").unwrap();
+        state.add_tag(boss, "large").unwrap();
+
+        assert_eq!(state.find_by_name("player"), Some(player));
+        assert_eq!(state.find_by_name("hero"), None);
+        assert_eq!(state.entities_with_tag("enemy"), vec![grunt, boss]);
+
+        assert!(state.remove_tag(grunt, "enemy"));
+        assert!(!state.remove_tag(grunt, "enemy"));
+        state.despawn(boss);
+        assert!(state.entities_with_tag("enemy").is_empty());
+        assert_eq!(state.find_by_name("boss"), None);
+        assert!(state.name_index.borrow().tags.is_empty());
+    }
+
+    #[test]
+    fn test_index_follows_bindings() {
+        let world = World::new();
+        let state = &world.state;
+        let a = state.spawn((Name::new("a"),));
+        assert_eq!(state.find_by_name("a"), Some(a));
+
+        let b = state.create_entity();
+        state.commands().bind(b, Tags(vec!["x".to_string()].into_iter().collect()));
+        state.apply_commands();
+        assert_eq!(state.entities_with_tag("x"), vec![b]);
+
+        state.bind_component(a, Name::new("c"));
+        assert_eq!(state.find_by_name("c"), Some(a));
+        assert!(!state.name_index.borrow().names.contains_key("a"));
+        state.unbind_component::<Tags>(b);
+        assert!(state.entities_with_tag("x").is_empty());
+        assert!(state.name_index.borrow().tags.is_empty());
+    }
+
+    #[test]
+    fn test_index_follows_mutation() {
+        let world = World::new();
+        let state = &world.state;
+        let a = state.spawn((Name::new("a"),));
+        let b = state.spawn((Name::new("b"), Tags::default()));
+
+        state.get_component_mut::<Name>(a).unwrap().0 = "c".to_string();
+        {
+            let mut query = state.query::<&mut Tags>().unwrap();
+            for (_, mut tags) in query.iter() {
+                tags.0.insert("x".to_string());
+            }
+        }
+        state.apply_commands();
+        assert_eq!(state.find_by_name("c"), Some(a));
+        assert!(!state.name_index.borrow().names.contains_key("a"));
+        assert_eq!(state.entities_with_tag("x"), vec![b]);
+
+        state.components_mut::<Name>().unwrap().get_mut(&b).unwrap().0 = "d".to_string();
+        state.components_mut::<Tags>().unwrap().get_mut(&b).unwrap().0.clear();
+        state.tick(0.1).unwrap();
+        assert_eq!(state.find_by_name("d"), Some(b));
+        assert_eq!(state.find_by_name("c"), Some(a));
+        assert!(!state.name_index.borrow().names.contains_key("b"));
+        assert!(state.name_index.borrow().tags.is_empty());
+    }
+}
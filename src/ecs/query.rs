@@ -0,0 +1,528 @@
+use std::any::type_name;
+use std::cell::{Ref, RefMut};
+use std::marker::PhantomData;
+
+use crate::error::{Error, Result};
+use crate::ecs::borrow::{hold, Mut, Outer};
+use crate::ecs::component::*;
+use crate::ecs::entity::Entity;
+use crate::ecs::signature::Signature;
+use crate::ecs::storage::{SparseSet, SparseIndex};
+use crate::ecs::world::WorldState;
+
+/// Component access that can be requested through `WorldState::query`
+///
+/// Implemented for `&C`, `&mut C`, `Option<&C>`, `Option<&mut C>` and tuples of those.
+pub trait WorldQuery {
+    /// Storage borrows held for the lifetime of the query
+    type Guard<'w>;
+    /// Per iteration lookup table built from the guards
+    type Table<'q>;
+    type Item<'q>;
+
+    /// Add the component ids the query requires to `required`
+    fn signature(manager: &ComponentManager, required: &mut Signature) -> Result<()>;
+    /// Borrow the storages, `tick` is the world change tick stamped by writes through `Mut`
+    fn borrow(store: &ComponentStoreProto, tick: u64) -> Result<Self::Guard<'_>>;
+    fn table<'q>(guard: &'q mut Self::Guard<'_>) -> Self::Table<'q>;
+    fn fetch<'q>(table: &mut Self::Table<'q>, entity: Entity) -> Option<Self::Item<'q>>;
+    fn fetch_one<'q>(guard: &'q mut Self::Guard<'_>, entity: Entity) -> Option<Self::Item<'q>>;
+}
+
+/// Entity filter that does not borrow any component data
+pub trait QueryFilter {
+    fn filter(manager: &ComponentManager, required: &mut Signature, excluded: &mut Signature) -> Result<()>;
+
+    /// Drop matched entities by their change ticks, compared against `since`
+    fn retain(_store: &ComponentStoreProto, _since: u64, _entities: &mut Vec<Entity>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Only match entities carrying component `C`
+pub struct With<C>(PhantomData<C>);
+
+/// Only match entities without component `C`
+pub struct Without<C>(PhantomData<C>);
+
+/// Only match entities whose component `C` was bound since the system last ran
+pub struct Added<C>(PhantomData<C>);
+
+/// Only match entities whose component `C` was bound or written through `Mut` since the system last ran
+pub struct Changed<C>(PhantomData<C>);
+
+fn component_id<C: 'static + Component>(manager: &ComponentManager) -> Result<usize> {
+    manager.get_code::<C>().copied().ok_or(Error::UnknownComponent(type_name::<C>()))
+}
+
+impl<C: 'static + Component> QueryFilter for With<C> {
+    fn filter(manager: &ComponentManager, required: &mut Signature, _excluded: &mut Signature) -> Result<()> {
+        required.insert(component_id::<C>(manager)?);
+        Ok(())
+    }
+}
+
+impl<C: 'static + Component> QueryFilter for Without<C> {
+    fn filter(manager: &ComponentManager, _required: &mut Signature, excluded: &mut Signature) -> Result<()> {
+        // Nothing can carry a component type that was never registered
+        if let Some(id) = manager.get_code::<C>() {
+            excluded.insert(*id);
+        }
+        Ok(())
+    }
+}
+
+impl<C: 'static + Component> QueryFilter for Added<C> {
+    fn filter(manager: &ComponentManager, required: &mut Signature, _excluded: &mut Signature) -> Result<()> {
+        required.insert(component_id::<C>(manager)?);
+        Ok(())
+    }
+
+    fn retain(store: &ComponentStoreProto, since: u64, entities: &mut Vec<Entity>) -> Result<()> {
+        let ticks = store.try_ticks::<C>()?;
+        entities.retain(|entity| ticks.get(entity).is_some_and(|ticks| ticks.is_added(since)));
+        Ok(())
+    }
+}
+
+impl<C: 'static + Component> QueryFilter for Changed<C> {
+    fn filter(manager: &ComponentManager, required: &mut Signature, _excluded: &mut Signature) -> Result<()> {
+        required.insert(component_id::<C>(manager)?);
+        Ok(())
+    }
+
+    fn retain(store: &ComponentStoreProto, since: u64, entities: &mut Vec<Entity>) -> Result<()> {
+        let ticks = store.try_ticks::<C>()?;
+        entities.retain(|entity| ticks.get(entity).is_some_and(|ticks| ticks.is_changed(since)));
+        Ok(())
+    }
+}
+
+impl<C: 'static + Component> WorldQuery for &C {
+    type Guard<'w> = Ref<'w, SparseSet<C>>;
+    type Table<'q> = &'q SparseSet<C>;
+    type Item<'q> = &'q C;
+
+    fn signature(manager: &ComponentManager, required: &mut Signature) -> Result<()> {
+        required.insert(component_id::<C>(manager)?);
+        Ok(())
+    }
+
+    fn borrow(store: &ComponentStoreProto, _tick: u64) -> Result<Self::Guard<'_>> {
+        store.try_get::<C>()
+    }
+
+    fn table<'q>(guard: &'q mut Self::Guard<'_>) -> Self::Table<'q> {
+        guard
+    }
+
+    fn fetch<'q>(table: &mut Self::Table<'q>, entity: Entity) -> Option<Self::Item<'q>> {
+        table.get(&entity)
+    }
+
+    fn fetch_one<'q>(guard: &'q mut Self::Guard<'_>, entity: Entity) -> Option<Self::Item<'q>> {
+        guard.get(&entity)
+    }
+}
+
+impl<C: 'static + Component> WorldQuery for &mut C {
+    type Guard<'w> = GuardMut<'w, C>;
+    type Table<'q> = RowsMut<'q, C>;
+    type Item<'q> = Mut<'q, C>;
+
+    fn signature(manager: &ComponentManager, required: &mut Signature) -> Result<()> {
+        required.insert(component_id::<C>(manager)?);
+        Ok(())
+    }
+
+    fn borrow(store: &ComponentStoreProto, tick: u64) -> Result<Self::Guard<'_>> {
+        Ok(GuardMut {
+            data: store.try_get_mut::<C>()?,
+            ticks: store.try_ticks_mut::<C>()?,
+            tick,
+        })
+    }
+
+    fn table<'q>(guard: &'q mut Self::Guard<'_>) -> Self::Table<'q> {
+        let (index, rows) = guard.data.split_mut();
+        let (tick_index, ticks) = guard.ticks.split_mut();
+        RowsMut {
+            index,
+            rows: rows.iter_mut().map(Some).collect(),
+            tick_index,
+            ticks: ticks.iter_mut().map(Some).collect(),
+            tick: guard.tick,
+        }
+    }
+
+    fn fetch<'q>(table: &mut Self::Table<'q>, entity: Entity) -> Option<Self::Item<'q>> {
+        let dense = table.index.dense_index(&entity)?;
+        let tick_dense = table.tick_index.dense_index(&entity)?;
+        let row = table.rows[dense].take()?;
+        let ticks = table.ticks[tick_dense].take()?;
+        Some(Mut::new(row, ticks, table.tick))
+    }
+
+    fn fetch_one<'q>(guard: &'q mut Self::Guard<'_>, entity: Entity) -> Option<Self::Item<'q>> {
+        let row = guard.data.get_mut(&entity)?;
+        let ticks = guard.ticks.get_mut(&entity)?;
+        Some(Mut::new(row, ticks, guard.tick))
+    }
+}
+
+/// Mutable borrow of a storage and its change ticks
+pub struct GuardMut<'w, C> {
+    data: RefMut<'w, SparseSet<C>>,
+    ticks: RefMut<'w, SparseSet<ComponentTicks>>,
+    tick: u64,
+}
+
+/// Mutable rows of a storage and their ticks, each row can be handed out once per iteration
+///
+/// The slots are collected for every `Query::iter` call, `benches/storage.rs` measures
+/// this at a few percent of a join.
+pub struct RowsMut<'q, C> {
+    index: SparseIndex<'q>,
+    rows: Vec<Option<&'q mut C>>,
+    tick_index: SparseIndex<'q>,
+    ticks: Vec<Option<&'q mut ComponentTicks>>,
+    tick: u64,
+}
+
+impl<Q: WorldQuery> WorldQuery for Option<Q> {
+    type Guard<'w> = Option<Q::Guard<'w>>;
+    type Table<'q> = Option<Q::Table<'q>>;
+    type Item<'q> = Option<Q::Item<'q>>;
+
+    fn signature(_manager: &ComponentManager, _required: &mut Signature) -> Result<()> {
+        Ok(())
+    }
+
+    fn borrow(store: &ComponentStoreProto, tick: u64) -> Result<Self::Guard<'_>> {
+        match Q::borrow(store, tick) {
+            Ok(guard) => Ok(Some(guard)),
+            Err(Error::UnknownComponent(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn table<'q>(guard: &'q mut Self::Guard<'_>) -> Self::Table<'q> {
+        guard.as_mut().map(|guard| Q::table(guard))
+    }
+
+    fn fetch<'q>(table: &mut Self::Table<'q>, entity: Entity) -> Option<Self::Item<'q>> {
+        Some(table.as_mut().and_then(|table| Q::fetch(table, entity)))
+    }
+
+    fn fetch_one<'q>(guard: &'q mut Self::Guard<'_>, entity: Entity) -> Option<Self::Item<'q>> {
+        Some(guard.as_mut().and_then(|guard| Q::fetch_one(guard, entity)))
+    }
+}
+
+impl QueryFilter for () {
+    fn filter(_manager: &ComponentManager, _required: &mut Signature, _excluded: &mut Signature) -> Result<()> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: WorldQuery),*> WorldQuery for ($($name,)*) {
+            type Guard<'w> = ($($name::Guard<'w>,)*);
+            type Table<'q> = ($($name::Table<'q>,)*);
+            type Item<'q> = ($($name::Item<'q>,)*);
+
+            fn signature(manager: &ComponentManager, required: &mut Signature) -> Result<()> {
+                $($name::signature(manager, required)?;)*
+                Ok(())
+            }
+
+            fn borrow(store: &ComponentStoreProto, tick: u64) -> Result<Self::Guard<'_>> {
+                Ok(($($name::borrow(store, tick)?,)*))
+            }
+
+            fn table<'q>(guard: &'q mut Self::Guard<'_>) -> Self::Table<'q> {
+                let ($($name,)*) = guard;
+                ($($name::table($name),)*)
+            }
+
+            fn fetch<'q>(table: &mut Self::Table<'q>, entity: Entity) -> Option<Self::Item<'q>> {
+                let ($($name,)*) = table;
+                Some(($($name::fetch($name, entity)?,)*))
+            }
+
+            fn fetch_one<'q>(guard: &'q mut Self::Guard<'_>, entity: Entity) -> Option<Self::Item<'q>> {
+                let ($($name,)*) = guard;
+                Some(($($name::fetch_one($name, entity)?,)*))
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            fn filter(manager: &ComponentManager, required: &mut Signature, excluded: &mut Signature) -> Result<()> {
+                $($name::filter(manager, required, excluded)?;)*
+                Ok(())
+            }
+
+            fn retain(store: &ComponentStoreProto, since: u64, entities: &mut Vec<Entity>) -> Result<()> {
+                $($name::retain(store, since, entities)?;)*
+                Ok(())
+            }
+        }
+    }
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+
+/// Borrowed view over all entities matching `Q` and the filter `F`
+///
+/// `Added` and `Changed` filters compare against the last run of the ticking
+/// system, see `WorldState::last_run`.
+///
+/// The storages stay borrowed until the query is dropped, so a conflicting
+/// borrow is reported once when the query is created instead of panicking.
+pub struct Query<'w, Q: WorldQuery, F: QueryFilter = ()> {
+    // Declared before `_store` so the storage guards are released first
+    guard: Q::Guard<'w>,
+    entities: Vec<Entity>,
+    _store: Outer<'w>,
+    _filter: PhantomData<F>,
+}
+
+impl<'w, Q: WorldQuery, F: QueryFilter> Query<'w, Q, F> {
+    pub(crate) fn new(state: &'w WorldState) -> Result<Self> {
+        let mut required = Signature::new();
+        let mut excluded = Signature::new();
+        {
+            let manager = state.component_manager.borrow();
+            Q::signature(&manager, &mut required)?;
+            F::filter(&manager, &mut required, &mut excluded)?;
+        }
+        let entities = state.entity_store.borrow().values()
+            .filter(|entry| entry.components.contains_all(&required) && !entry.components.intersects(&excluded))
+            .map(|entry| entry.entity)
+            .collect::<Vec<_>>();
+
+        // SAFETY: `store` is kept in the query and the guards are dropped before it
+        let (store, proto) = unsafe { hold(&state.component_store) }
+            .map_err(|_| Error::BorrowConflict("component store"))?;
+        let mut entities = entities;
+        F::retain(proto, state.last_run(), &mut entities)?;
+        let guard = Q::borrow(proto, state.change_tick())?;
+        entities.sort();
+        Ok(Self {
+            guard,
+            entities,
+            _store: store,
+            _filter: PhantomData,
+        })
+    }
+
+    /// Matching entities, sorted by handle
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.binary_search(&entity).is_ok()
+    }
+
+    /// Fetch the components of a single matching entity
+    pub fn get(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        if !self.contains(entity) {
+            return None;
+        }
+        Q::fetch_one(&mut self.guard, entity)
+    }
+
+    pub fn iter(&mut self) -> QueryIter<'_, Q> {
+        QueryIter {
+            table: Q::table(&mut self.guard),
+            entities: self.entities.iter(),
+        }
+    }
+}
+
+impl<'q, 'w, Q: WorldQuery, F: QueryFilter> IntoIterator for &'q mut Query<'w, Q, F> {
+    type Item = (Entity, Q::Item<'q>);
+    type IntoIter = QueryIter<'q, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct QueryIter<'q, Q: WorldQuery> {
+    table: Q::Table<'q>,
+    entities: std::slice::Iter<'q, Entity>,
+}
+
+impl<'q, Q: WorldQuery> Iterator for QueryIter<'q, Q> {
+    type Item = (Entity, Q::Item<'q>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entity in self.entities.by_ref() {
+            if let Some(item) = Q::fetch(&mut self.table, *entity) {
+                return Some((*entity, item));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.entities.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::ecs::system::{System, SystemContext};
+
+    struct Position(i32);
+    impl Component for Position {}
+
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    struct Frozen;
+    impl Component for Frozen {}
+
+    #[test]
+    fn test_query_join_and_filters() {
+        let state = WorldState::new();
+        state.register_component::<Frozen>();
+        let a = state.create_entity();
+        let b = state.create_entity();
+        let c = state.create_entity();
+        for (entity, x) in [(a, 1), (b, 2), (c, 3)].iter() {
+            state.bind_component(*entity, Position(*x));
+        }
+        state.bind_component(a, Velocity(10));
+        state.bind_component(b, Velocity(20));
+        state.bind_component(b, Frozen);
+
+        {
+            let mut query = state.query::<(&mut Position, &Velocity)>().unwrap();
+            for (_, (mut position, velocity)) in query.iter() {
+                position.0 += velocity.0;
+            }
+        }
+        {
+            let mut query = state.query_filtered::<(&Position,), Without<Frozen>>().unwrap();
+            let items: Vec<_> = query.iter().map(|(entity, (position,))| (entity, position.0)).collect();
+            assert_eq!(items, vec![(a, 11), (c, 3)]);
+        }
+        {
+            let mut query = state.query::<(&Position, Option<&Velocity>)>().unwrap();
+            assert_eq!(query.len(), 3);
+            let (position, velocity) = query.get(c).unwrap();
+            assert_eq!(position.0, 3);
+            assert!(velocity.is_none());
+        }
+        let mut query = state.query_filtered::<&Position, With<Frozen>>().unwrap();
+        assert_eq!(query.iter().map(|(_, position)| position.0).collect::<Vec<_>>(), vec![22]);
+    }
+
+    #[test]
+    fn test_query_borrow_conflict() {
+        let state = WorldState::new();
+        state.register_component::<Position>();
+        let _reading = state.query::<&Position>().unwrap();
+        assert_eq!(
+            state.query::<&mut Position>().err(),
+            Some(Error::BorrowConflict(type_name::<Position>()))
+        );
+        assert!(state.query::<&Velocity>().is_err());
+    }
+
+    type Seen = Rc<RefCell<Vec<(Vec<Entity>, Vec<Entity>, Vec<Entity>)>>>;
+
+    struct Watch(Seen);
+
+    impl System for Watch {
+        fn tick(&mut self, ctx: &SystemContext) {
+            let added = ctx.world.query_filtered::<&Position, Added<Position>>().unwrap().entities().to_vec();
+            let changed = ctx.world.query_filtered::<&Position, Changed<Position>>().unwrap().entities().to_vec();
+            self.0.borrow_mut().push((added, changed, ctx.world.removed::<Position>()));
+        }
+    }
+
+    #[test]
+    fn test_change_detection() {
+        let state = WorldState::new();
+        let seen = Seen::default();
+        state.register_system("watch", Watch(seen.clone())).unwrap();
+        let a = state.create_entity();
+        let b = state.create_entity();
+        state.bind_component(a, Position(1));
+        state.bind_component(b, Position(2));
+
+        state.tick(0.).unwrap();
+        state.tick(0.).unwrap();
+        state.get_component_mut::<Position>(a).unwrap().0 += 1;
+        state.unbind_component::<Position>(b);
+        state.tick(0.).unwrap();
+        // Only writes through the guard count, reads and untouched rows do not
+        for (_, position) in state.query::<&mut Position>().unwrap().iter() {
+            assert_eq!(position.0, 2);
+        }
+        state.tick(0.).unwrap();
+        state.bind_component(b, Position(3));
+        state.tick(0.).unwrap();
+        for (entity, mut position) in state.query::<&mut Position>().unwrap().iter() {
+            if entity == b {
+                position.0 += 1;
+            }
+        }
+        state.tick(0.).unwrap();
+
+        assert_eq!(*seen.borrow(), vec![
+            (vec![a, b], vec![a, b], vec![]),
+            (vec![], vec![], vec![]),
+            (vec![], vec![a], vec![b]),
+            (vec![], vec![], vec![]),
+            (vec![b], vec![b], vec![]),
+            (vec![], vec![b], vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_rebind_marks_changed() {
+        let state = WorldState::new();
+        let seen = Seen::default();
+        state.register_system("watch", Watch(seen.clone())).unwrap();
+        let entity = state.create_entity();
+        state.bind_component(entity, Position(1));
+        state.tick(0.).unwrap();
+
+        assert!(matches!(state.replace_component(entity, Position(2)), Ok(Some(Position(1)))));
+        state.tick(0.).unwrap();
+        assert!(state.bind_component(entity, Position(3)));
+        state.tick(0.).unwrap();
+
+        assert_eq!(*seen.borrow(), vec![
+            (vec![entity], vec![entity], vec![]),
+            (vec![], vec![entity], vec![]),
+            (vec![], vec![entity], vec![]),
+        ]);
+    }
+}
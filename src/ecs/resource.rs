@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::any::{type_name, Any, TypeId};
+use std::cell::{RefCell, Ref, RefMut};
+
+use crate::error::{Error, Result};
+
+pub type ResourceStore = RefCell<ResourceStoreProto>;
+
+/// Typed global values shared by systems and stages, one per type
+pub struct ResourceStoreProto {
+    store: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl ResourceStoreProto {
+    pub fn new() -> ResourceStore {
+        RefCell::new(Self { store: HashMap::new() })
+    }
+
+    /// Insert the resource, returning the one it replaced
+    pub fn insert<R: 'static>(&mut self, resource: R) -> Option<R> {
+        self.store.insert(TypeId::of::<R>(), Box::new(RefCell::new(resource)))
+            .and_then(|old| old.downcast::<RefCell<R>>().ok())
+            .map(|old| old.into_inner())
+    }
+
+    pub fn remove<R: 'static>(&mut self) -> Option<R> {
+        self.store.remove(&TypeId::of::<R>())
+            .and_then(|old| old.downcast::<RefCell<R>>().ok())
+            .map(|old| old.into_inner())
+    }
+
+    pub fn contains<R: 'static>(&self) -> bool {
+        self.store.contains_key(&TypeId::of::<R>())
+    }
+
+    pub fn cell<R: 'static>(&self) -> Option<&RefCell<R>> {
+        self.store.get(&TypeId::of::<R>()).and_then(|resource| resource.downcast_ref::<RefCell<R>>())
+    }
+
+    pub fn try_get<R: 'static>(&self) -> Result<Ref<'_, R>> {
+        self.cell::<R>().ok_or(Error::UnknownResource(type_name::<R>()))?
+            .try_borrow().map_err(|_| Error::BorrowConflict(type_name::<R>()))
+    }
+
+    pub fn try_get_mut<R: 'static>(&self) -> Result<RefMut<'_, R>> {
+        self.cell::<R>().ok_or(Error::UnknownResource(type_name::<R>()))?
+            .try_borrow_mut().map_err(|_| Error::BorrowConflict(type_name::<R>()))
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::core::{BasicMesh, SimpleMesh, ComplexMesh, MeshRecipe};
+use crate::ecs::component::Component;
+use crate::ecs::components::*;
+use crate::ecs::entity::Entity;
+use crate::ecs::world::WorldState;
+use crate::error::{Error, Result};
+
+/// Serialized world contents, one record per entity
+///
+/// ```json
+/// {"entities": [{"parent": null, "components": {"name": "player", "transform": {...}}}]}
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneEntity {
+    /// Position of the parent in `Scene::entities`
+    #[serde(default)]
+    pub parent: Option<usize>,
+    /// Components keyed by the name they were registered under
+    #[serde(default)]
+    pub components: BTreeMap<String, Value>,
+}
+
+impl Scene {
+    pub fn from_json(text: &str) -> Result<Self> {
+        serde_json::from_str(text).map_err(|err| Error::Scene(err.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|err| Error::Scene(err.to_string()))
+    }
+}
+
+type SaveFn = Box<dyn Fn(&WorldState, Entity) -> Option<Result<Value>>>;
+/// Binds a component decoded ahead of spawning
+type BindFn = Box<dyn FnOnce(&WorldState, Entity)>;
+type LoadFn = Box<dyn Fn(Value) -> Result<BindFn>>;
+
+struct SceneType {
+    name: String,
+    save: SaveFn,
+    load: LoadFn,
+}
+
+/// Component types written to and read from scenes, unregistered components are skipped on save
+#[derive(Default)]
+pub struct SceneRegistry {
+    types: Vec<SceneType>,
+}
+
+impl SceneRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the component as is under `name`
+    pub fn register<C>(&mut self, name: &str)
+        where C: 'static + Component + Serialize + DeserializeOwned
+    {
+        self.register_with::<C, C, _, _>(name, |component| serialize(component), |data| data);
+    }
+
+    /// Store the component through a serializable stand-in `D`
+    pub fn register_with<C, D, S, L>(&mut self, name: &str, save: S, load: L)
+        where C: 'static + Component,
+              D: 'static + Serialize + DeserializeOwned,
+              S: 'static + Fn(&C) -> Result<Value>,
+              L: 'static + Fn(D) -> C,
+    {
+        self.types.retain(|scene_type| scene_type.name != name);
+        self.types.push(SceneType {
+            name: name.to_string(),
+            save: Box::new(move |state, entity| state.get_component::<C>(entity).map(|component| save(&component))),
+            load: Box::new(move |value| {
+                let data = serde_json::from_value::<D>(value).map_err(|err| Error::Scene(err.to_string()))?;
+                let component = load(data);
+                Ok(Box::new(move |state: &WorldState, entity| { state.bind_component(entity, component); }) as BindFn)
+            }),
+        });
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.types.iter().any(|scene_type| scene_type.name == name)
+    }
+
+    /// Registered names in registration order
+    pub fn names(&self) -> Vec<&str> {
+        self.types.iter().map(|scene_type| scene_type.name.as_str()).collect()
+    }
+}
+
+fn serialize<D: Serialize>(data: &D) -> Result<Value> {
+    serde_json::to_value(data).map_err(|err| Error::Scene(err.to_string()))
+}
+
+/// Concrete mesh behind a `MeshComponent`
+#[derive(Serialize, Deserialize)]
+enum MeshData {
+    Basic(BasicMesh),
+    Simple(SimpleMesh),
+    Complex(ComplexMesh),
+}
+
+fn save_mesh(mesh: &MeshComponent) -> Result<Value> {
+    match mesh.cook() {
+        MeshRecipe::Basic { data } => serialize(&MeshData::Basic(data.clone())),
+        MeshRecipe::Simple { data } => serialize(&MeshData::Simple(data.clone())),
+        MeshRecipe::Complex { data } => serialize(&MeshData::Complex(data.clone())),
+    }
+}
+
+fn load_mesh(data: MeshData) -> MeshComponent {
+    match data {
+        MeshData::Basic(mesh) => Box::new(mesh),
+        MeshData::Simple(mesh) => Box::new(mesh),
+        MeshData::Complex(mesh) => Box::new(mesh),
+    }
+}
+
+/// Registry with the engine components, as installed by `World::new`
+pub(crate) fn default_registry() -> SceneRegistry {
+    let mut registry = SceneRegistry::new();
+    registry.register::<Name>("name");
+    registry.register::<Tags>("tags");
+    registry.register::<Persistent>("persistent");
+    registry.register::<TransformComponent>("transform");
+    registry.register::<CameraComponent>("camera");
+    registry.register::<ShapeComponent>("shape");
+    registry.register::<WidgetComponent>("widget");
+    registry.register_with::<MeshComponent, MeshData, _, _>("mesh", save_mesh, load_mesh);
+    registry
+}
+
+/// Scene saving and loading
+impl WorldState {
+    /// Let scenes carry the component under `name`
+    pub fn register_scene_component<C>(&self, name: &str)
+        where C: 'static + Component + Serialize + DeserializeOwned
+    {
+        self.register_component::<C>();
+        self.scene_registry.borrow_mut().register::<C>(name);
+    }
+
+    /// Capture every entity with its registered components and hierarchy
+    pub fn save_scene(&self) -> Result<Scene> {
+        let mut entities: Vec<Entity> = self.entity_store.borrow().keys().copied().collect();
+        entities.sort();
+        let registry = self.scene_registry.borrow();
+        let mut scene = Scene::default();
+        for entity in entities.iter() {
+            let mut record = SceneEntity {
+                parent: self.parent(*entity).and_then(|parent| entities.binary_search(&parent).ok()),
+                ..SceneEntity::default()
+            };
+            for scene_type in registry.types.iter() {
+                if let Some(value) = (scene_type.save)(self, *entity) {
+                    record.components.insert(scene_type.name.clone(), value?);
+                }
+            }
+            scene.entities.push(record);
+        }
+        Ok(scene)
+    }
+
+    /// Spawn the scene entities into the world, returns them in scene order
+    ///
+    /// Every component is decoded and every parent link checked first, so a scene
+    /// with unregistered components, bad data or a broken hierarchy spawns nothing.
+    pub fn load_scene(&self, scene: &Scene) -> Result<Vec<Entity>> {
+        let registry = self.scene_registry.borrow();
+        let mut binds: Vec<Vec<BindFn>> = Vec::with_capacity(scene.entities.len());
+        for record in scene.entities.iter() {
+            if let Some(name) = record.components.keys().find(|name| !registry.contains(name)) {
+                return Err(Error::Scene(format!("component {} is not registered for scenes", name)));
+            }
+            let decoded = registry.types.iter()
+                .filter_map(|scene_type| record.components.get(&scene_type.name).map(|value| (scene_type.load)(value.clone())))
+                .collect::<Result<Vec<_>>>()?;
+            binds.push(decoded);
+        }
+        check_hierarchy(scene)?;
+
+        let entities: Vec<Entity> = scene.entities.iter().map(|_| self.create_entity()).collect();
+        for (decoded, entity) in binds.into_iter().zip(entities.iter()) {
+            for bind in decoded {
+                bind(self, *entity);
+            }
+        }
+        for (record, entity) in scene.entities.iter().zip(entities.iter()) {
+            if let Some(parent) = record.parent {
+                self.set_parent(*entity, entities[parent])?;
+            }
+        }
+        Ok(entities)
+    }
+}
+
+/// Parents must be in range and must not lead back to the entity
+fn check_hierarchy(scene: &Scene) -> Result<()> {
+    let count = scene.entities.len();
+    for (index, record) in scene.entities.iter().enumerate() {
+        let mut parent = record.parent;
+        let mut depth = 0;
+        while let Some(next) = parent {
+            if next >= count {
+                return Err(Error::Scene(format!("parent {} is out of range", next)));
+            }
+            depth += 1;
+            if next == index || depth > count {
+                return Err(Error::Scene(format!("entity {} is its own ancestor", index)));
+            }
+            parent = scene.entities[next].parent;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{BasicMesh, Point3};
+    use crate::ecs::*;
+    use crate::ecs::scene::Scene;
+    use crate::error::Error;
+
+    #[test]
+    fn test_scene_round_trip() {
+        let world = World::new();
+        let state = &world.state;
+        let root = state.create_entity();
+        let child = state.create_entity();
+        let mut transform = TransformComponent::default();
+        transform.set_translation_xyz(1., 2., 3.);
+        state.bind_component(root, transform);
+        state.bind_component(root, BasicMesh::new(vec![Point3::origin(), Point3::new(0., 1., 0.)], vec![1]));
+        state.set_name(root, "root").unwrap();
+        state.add_tag(child, "leaf").unwrap();
+        state.set_parent(child, root).unwrap();
+
+        let text = state.save_scene().unwrap().to_json().unwrap();
+        let scene = Scene::from_json(&text).unwrap();
+        assert_eq!(scene, state.save_scene().unwrap());
+
+        let loaded = World::new();
+        let entities = loaded.state.load_scene(&scene).unwrap();
+        let root = loaded.state.find_by_name("root").unwrap();
+        assert_eq!(root, entities[0]);
+        assert_eq!(loaded.state.children(root), vec![entities[1]]);
+        assert_eq!(loaded.state.entities_with_tag("leaf"), vec![entities[1]]);
+        assert_eq!(loaded.state.get_component::<TransformComponent>(root).unwrap().translation().z, 3.);
+        assert!(loaded.state.has_component::<MeshComponent>(root));
+        assert_eq!(loaded.state.save_scene().unwrap(), scene);
+
+        let unknown = Scene::from_json(r#"{"entities": [{"components": {"health": 3}}]}"#).unwrap();
+        assert!(matches!(loaded.state.load_scene(&unknown), Err(Error::Scene(_))));
+    }
+
+    #[test]
+    fn test_failed_load_spawns_nothing() {
+        let world = World::new();
+        let state = &world.state;
+        let bad_data = Scene::from_json(r#"{"entities": [
+            {"components": {"name": "ok"}},
+            {"components": {"transform": "not a transform"}}
+        ]}"#).unwrap();
+        let looped = Scene::from_json(r#"{"entities": [{"parent": 1}, {"parent": 0}]}"#).unwrap();
+        let dangling = Scene::from_json(r#"{"entities": [{"components": {"name": "ok"}, "parent": 5}]}"#).unwrap();
+
+        for scene in [bad_data, looped, dangling].iter() {
+            assert!(matches!(state.load_scene(scene), Err(Error::Scene(_))));
+            assert_eq!(state.entity_store.borrow().len(), 0);
+        }
+        assert_eq!(state.find_by_name("ok"), None);
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+const BITS: usize = 64;
+
+/// Growable bitset describing which component types an entity carries
+///
+/// Bit `n` is the component id handed out by `ComponentManager::register_component`.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Signature {
+    blocks: Vec<u64>,
+}
+
+impl Signature {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_ids(ids: &[usize]) -> Self {
+        let mut signature = Self::new();
+        for id in ids {
+            signature.insert(*id);
+        }
+        signature
+    }
+
+    /// Builder style insert
+    pub fn with(mut self, id: usize) -> Self {
+        self.insert(id);
+        self
+    }
+
+    pub fn insert(&mut self, id: usize) {
+        let block = id / BITS;
+        if block >= self.blocks.len() {
+            self.blocks.resize(block + 1, 0);
+        }
+        self.blocks[block] |= 1 << (id % BITS);
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        if let Some(block) = self.blocks.get_mut(id / BITS) {
+            *block &= !(1 << (id % BITS));
+        }
+        while self.blocks.last() == Some(&0) {
+            self.blocks.pop();
+        }
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.blocks.get(id / BITS).is_some_and(|block| block & (1 << (id % BITS)) != 0)
+    }
+
+    /// True if every bit of `other` is set in self
+    pub fn contains_all(&self, other: &Signature) -> bool {
+        other.blocks.iter().enumerate().all(|(i, bits)| {
+            self.blocks.get(i).map_or(0, |block| *block) & bits == *bits
+        })
+    }
+
+    /// True if self and `other` share at least one bit
+    pub fn intersects(&self, other: &Signature) -> bool {
+        self.blocks.iter().zip(other.blocks.iter()).any(|(a, b)| a & b != 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// Iterate over the set component ids
+    pub fn ids(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks.iter().enumerate().flat_map(|(i, block)| {
+            (0..BITS).filter(move |bit| block & (1 << bit) != 0).map(move |bit| i * BITS + bit)
+        })
+    }
+}
+
+impl fmt::Debug for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.ids()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_bits() {
+        let mut signature = Signature::from_ids(&[1, 70, 200]);
+        assert!(signature.contains(70));
+        assert!(!signature.contains(2));
+        assert!(signature.contains_all(&Signature::from_ids(&[1, 200])));
+        assert!(!signature.contains_all(&Signature::from_ids(&[1, 3])));
+        assert!(signature.intersects(&Signature::new().with(3).with(200)));
+        assert_eq!(signature.ids().collect::<Vec<_>>(), vec![1, 70, 200]);
+
+        signature.remove(200);
+        signature.remove(70);
+        assert_eq!(signature, Signature::from_ids(&[1]));
+        assert!(Signature::from_ids(&[1]).contains_all(&Signature::new()));
+    }
+}
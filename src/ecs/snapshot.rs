@@ -0,0 +1,366 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+
+use crate::ecs::component::*;
+use crate::ecs::components::*;
+use crate::ecs::entity::*;
+use crate::ecs::storage::SparseSet;
+use crate::ecs::time::Time;
+use crate::ecs::world::WorldState;
+use crate::error::{Error, Result};
+
+/// Copy and write back of one component type, registered by `WorldState::register_snapshot_component`
+#[derive(Clone, Copy)]
+pub struct Snapshotter {
+    capture: fn(&ComponentStoreProto) -> Result<Box<dyn Any>>,
+    copy: fn(&dyn Any) -> Box<dyn Any>,
+}
+
+type ColumnCopy<C> = (SparseSet<C>, SparseSet<ComponentTicks>);
+
+fn capture<C: 'static + Component + Clone>(store: &ComponentStoreProto) -> Result<Box<dyn Any>> {
+    let data = store.try_get::<C>()?.clone();
+    let ticks = store.try_ticks::<C>()?.clone();
+    Ok(Box::new((data, ticks)))
+}
+
+fn copy<C: 'static + Component + Clone>(copy: &dyn Any) -> Box<dyn Any> {
+    match copy.downcast_ref::<ColumnCopy<C>>() {
+        Some(copy) => Box::new(copy.clone()),
+        None => Box::new(()),
+    }
+}
+
+/// Owned copy of the entities, the snapshot enabled component stores, the frame timing
+/// and the change ticks of the world and its systems
+pub struct Snapshot {
+    entity_manager: EntityManager,
+    entities: SparseSet<EntityEntry>,
+    components: HashMap<TypeId, Box<dyn Any>>,
+    active_camera: Option<Entity>,
+    time: Option<Time>,
+    change_tick: u64,
+    last_run: u64,
+    system_runs: Vec<(String, u64)>,
+}
+
+impl Snapshot {
+    /// Number of entities alive when the snapshot was taken
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+/// Frame durations and inputs of a recorded session
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputLog<I> {
+    frames: Vec<InputFrame<I>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputFrame<I> {
+    pub delta: f32,
+    pub inputs: Vec<I>,
+}
+
+impl<I> InputLog<I> {
+    pub fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    pub fn push(&mut self, delta: f32, inputs: Vec<I>) {
+        self.frames.push(InputFrame { delta, inputs });
+    }
+
+    pub fn frames(&self) -> &[InputFrame<I>] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+impl<I> Default for InputLog<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Snapshot enable the engine components, called by `World::new`
+pub(crate) fn register_defaults(state: &WorldState) {
+    state.register_snapshot_component::<MeshComponent>();
+    state.register_snapshot_component::<TransformComponent>();
+    state.register_snapshot_component::<CameraComponent>();
+    state.register_snapshot_component::<ShapeComponent>();
+    state.register_snapshot_component::<WidgetComponent>();
+    state.register_snapshot_component::<Parent>();
+    state.register_snapshot_component::<Children>();
+    state.register_snapshot_component::<GlobalTransform>();
+    state.register_snapshot_component::<StageOwned>();
+    state.register_snapshot_component::<Persistent>();
+    state.register_snapshot_component::<Name>();
+    state.register_snapshot_component::<Tags>();
+}
+
+/// Snapshots, rollback and input replay
+impl WorldState {
+    /// Include the component type in snapshots
+    pub fn register_snapshot_component<C: 'static + Component + Clone>(&self) {
+        self.register_component::<C>();
+        self.snapshotters.borrow_mut().insert(TypeId::of::<C>(), Snapshotter {
+            capture: capture::<C>,
+            copy: copy::<C>,
+        });
+    }
+
+    /// Copy the world for a later `restore`
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        let store = self.component_store.try_borrow().map_err(|_| Error::BorrowConflict("component store"))?;
+        let mut components = HashMap::new();
+        for (type_id, snapshotter) in self.snapshotters.borrow().iter() {
+            components.insert(*type_id, (snapshotter.capture)(&store)?);
+        }
+        Ok(Snapshot {
+            entity_manager: self.entity_manager.borrow().clone(),
+            entities: self.entity_store.borrow().clone(),
+            components,
+            active_camera: self.active_camera.get(),
+            time: self.resource::<Time>().map(|time| *time),
+            change_tick: self.change_tick(),
+            last_run: self.last_run(),
+            system_runs: self.system_store.try_borrow().map_err(|_| Error::BorrowConflict("system store"))?.last_runs(),
+        })
+    }
+
+    /// Roll the world back to the snapshot
+    ///
+    /// Entities created since are dropped. Component types without snapshot support keep
+    /// their current rows for entities that exist in both, and signatures are rebuilt from
+    /// those rows. Change ticks of the world and its systems are rewound, so replaying an
+    /// `InputLog` sees the same `Added`/`Changed` results as the recorded run.
+    ///
+    /// Not restored: buffered events are dropped while event counts keep growing, so the
+    /// cursors of existing `EventReader`s stay valid, and queued `Commands` are discarded.
+    /// Take snapshots between ticks so neither holds anything a replay depends on.
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<()> {
+        {
+            // Every borrow is taken before the first write, a conflict leaves the world as it was
+            let store = self.component_store.try_borrow().map_err(|_| Error::BorrowConflict("component store"))?;
+            let manager = self.component_manager.try_borrow().map_err(|_| Error::BorrowConflict("component manager"))?;
+            let mut entity_manager = self.entity_manager.try_borrow_mut()
+                .map_err(|_| Error::BorrowConflict(type_name::<EntityManager>()))?;
+            let mut entities = self.entity_store.try_borrow_mut().map_err(|_| Error::BorrowConflict("entity store"))?;
+            let mut name_index = self.name_index.try_borrow_mut().map_err(|_| Error::BorrowConflict("name index"))?;
+            let systems = self.system_store.try_borrow().map_err(|_| Error::BorrowConflict("system store"))?;
+            let resources = self.resource_store.try_borrow_mut().map_err(|_| Error::BorrowConflict("resource store"))?;
+            let mut locks = Vec::new();
+            for (type_id, storage) in store.borrow().iter() {
+                locks.push((*type_id, storage.try_lock()?));
+            }
+
+            *entity_manager = snapshot.entity_manager.clone();
+            *entities = snapshot.entities.clone();
+            let snapshotters = self.snapshotters.borrow();
+            for (type_id, lock) in locks.iter_mut() {
+                match (snapshot.components.get(type_id), snapshotters.get(type_id)) {
+                    (Some(copy), Some(snapshotter)) => lock.replace((snapshotter.copy)(copy.as_ref())),
+                    _ => {
+                        lock.retain(&|entity| entities.contains_key(&entity));
+                        // Signature bits of the other types follow the rows that are actually there
+                        if let Some(comp_id) = manager.id_of(type_id) {
+                            for entry in entities.values_mut() {
+                                if lock.contains(entry.entity) {
+                                    entry.components.insert(comp_id);
+                                } else {
+                                    entry.components.remove(comp_id);
+                                }
+                            }
+                        }
+                    }
+                }
+                lock.clear_removed();
+            }
+
+            self.active_camera.set(snapshot.active_camera);
+            if let (Some(time), Ok(mut current)) = (snapshot.time, resources.try_get_mut::<Time>()) {
+                *current = time;
+            }
+            self.set_change_ticks(snapshot.change_tick, snapshot.last_run);
+            for (name, tick) in snapshot.system_runs.iter() {
+                // Systems registered since the snapshot keep their own tick
+                let _ = systems.set_last_run(name, *tick);
+            }
+            *name_index = Default::default();
+        }
+        self.update_events();
+        self.update_events();
+        self.command_queue.clear();
+
+        let entities: Vec<Entity> = self.entity_store.borrow().keys().copied().collect();
+        for entity in entities {
+            self.index_entity(entity);
+        }
+        Ok(())
+    }
+
+    /// Send the inputs as events and tick, appending the frame to the log
+    pub fn tick_recorded<I: 'static + Clone>(&self, log: &mut InputLog<I>, delta: f32, inputs: Vec<I>) -> Result<()> {
+        for input in inputs.iter() {
+            self.send(input.clone());
+        }
+        log.push(delta, inputs);
+        self.tick(delta)
+    }
+
+    /// Feed a recorded session back through `tick`, usually right after restoring its starting snapshot
+    pub fn replay<I: 'static + Clone>(&self, log: &InputLog<I>) -> Result<()> {
+        for frame in log.frames() {
+            for input in frame.inputs.iter() {
+                self.send(input.clone());
+            }
+            self.tick(frame.delta)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Push {
+        Left,
+        Right,
+    }
+
+    #[derive(Default)]
+    struct Mover {
+        reader: EventReader<Push>,
+    }
+
+    impl System for Mover {
+        fn tick(&mut self, ctx: &SystemContext) {
+            let pushes: Vec<Push> = match ctx.world.events::<Push>() {
+                Some(events) => self.reader.read(&events).copied().collect(),
+                None => return,
+            };
+            let mut query = ctx.world.query::<&mut TransformComponent>().unwrap();
+            for (_, mut transform) in query.iter() {
+                for push in pushes.iter() {
+                    let step = if *push == Push::Left { -1. } else { 1. };
+                    transform.prepend_translation(crate::core::Vector3::new(step, 0., 0.));
+                }
+            }
+        }
+    }
+
+    fn positions(state: &WorldState) -> Vec<(Entity, f32)> {
+        let mut query = state.query::<&TransformComponent>().unwrap();
+        query.iter().map(|(entity, transform)| (entity, transform.translation().x)).collect()
+    }
+
+    #[test]
+    fn test_restore_and_replay() {
+        let world = World::new();
+        let state = &world.state;
+        state.register_system("mover", Mover::default()).unwrap();
+        let cube = state.spawn((TransformComponent::default(),));
+        state.set_name(cube, "cube").unwrap();
+        let start = state.snapshot().unwrap();
+
+        let mut log = InputLog::new();
+        state.tick_recorded(&mut log, 0.5, vec![Push::Right, Push::Right]).unwrap();
+        let spawned = state.spawn((TransformComponent::default(),));
+        state.tick_recorded(&mut log, 0.5, vec![Push::Left]).unwrap();
+        let recorded = positions(state);
+        let recorded_tick = state.change_tick();
+        assert_eq!(recorded, vec![(cube, 1.), (spawned, -1.)]);
+        state.despawn(cube);
+
+        state.restore(&start).unwrap();
+        assert_eq!(positions(state), vec![(cube, 0.)]);
+        assert!(!state.is_alive(spawned));
+        assert_eq!(state.find_by_name("cube"), Some(cube));
+        assert_eq!(state.time().frame_count(), 0);
+
+        state.replay(&log).unwrap();
+        assert_eq!(positions(state), vec![(cube, 1.)]);
+        assert_eq!(log.len(), 2);
+        assert_eq!(state.change_tick(), recorded_tick);
+    }
+
+    struct Scratch(u32);
+    impl Component for Scratch {}
+
+    #[test]
+    fn test_restore_components_without_snapshot() {
+        let world = World::new();
+        let state = &world.state;
+        let kept = state.spawn((TransformComponent::default(), Scratch(1)));
+        let other = state.spawn((TransformComponent::default(),));
+        let start = state.snapshot().unwrap();
+
+        state.bind_component(other, Scratch(2));
+        state.despawn(kept);
+        let late = state.spawn((Scratch(3),));
+        state.commands().bind(other, Persistent);
+        state.tick(0.1).unwrap();
+        state.commands().bind(other, Persistent);
+
+        state.restore(&start).unwrap();
+        // The despawn dropped the row of `kept`, the later bind on `other` stays
+        assert!(state.is_alive(kept) && !state.is_alive(late));
+        assert!(!state.has_component::<Scratch>(kept));
+        assert!(state.get_component::<Scratch>(kept).is_none());
+        assert!(state.has_component::<Scratch>(other));
+        assert_eq!(state.get_component::<Scratch>(other).unwrap().0, 2);
+        let mut query = state.query::<(&TransformComponent, &Scratch)>().unwrap();
+        assert_eq!(query.iter().map(|(entity, _)| entity).collect::<Vec<_>>(), vec![other]);
+        drop(query);
+
+        // Queued commands are dropped, the ticks are rewound
+        assert!(state.command_queue.is_empty());
+        assert!(!state.has_component::<Persistent>(other));
+        assert_eq!(state.change_tick(), start.change_tick);
+    }
+
+    #[test]
+    fn test_restore_while_borrowed() {
+        let world = World::new();
+        let state = &world.state;
+        let cube = state.spawn((TransformComponent::default(),));
+        let start = state.snapshot().unwrap();
+        state.despawn(cube);
+        let sphere = state.spawn((TransformComponent::default(), Scratch(1)));
+        state.set_name(sphere, "sphere").unwrap();
+
+        // The held column fails the restore before any other column or the entities are written
+        let query = state.query::<&TransformComponent>().unwrap();
+        assert!(matches!(state.restore(&start), Err(Error::BorrowConflict(_))));
+        drop(query);
+        assert!(!state.is_alive(cube) && state.is_alive(sphere));
+        assert!(state.has_component::<Scratch>(sphere));
+        assert_eq!(state.find_by_name("sphere"), Some(sphere));
+        assert_eq!(positions(state), vec![(sphere, 0.)]);
+
+        state.restore(&start).unwrap();
+        assert!(state.is_alive(cube) && !state.is_alive(sphere));
+        assert_eq!(state.find_by_name("sphere"), None);
+        assert_eq!(positions(state), vec![(cube, 0.)]);
+    }
+}
@@ -1,15 +1,44 @@
 use std::cell::{Ref, RefMut, RefCell};
+use std::rc::Rc;
 use std::any::Any;
 use std::collections::HashMap;
 
+use crate::error::{Error, Result};
+use crate::ecs::world::WorldState;
 
+/// Transition requested by the active stage at the end of its tick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trans {
+    None,
+    /// Pause the active stage and enter the named one on top of it
+    Push(String),
+    /// Exit the active stage and resume the one below
+    Pop,
+    /// Exit the active stage and enter the named one in its place
+    Switch(String),
+    /// Exit every stage and stop the world
+    Quit,
+}
+
+/// A game state on the world's stage stack, only the top one is ticked
+///
+/// Hooks may use the world freely. Stack changes they make through the world,
+/// such as `WorldState::push_stage`, are queued and applied once the hook returns.
 pub trait Stage {
-    fn on_enter(&mut self) {}
-    fn tick(&mut self) {}
-    fn on_exit(&mut self) {}
+    fn on_enter(&mut self, _world: &WorldState) {}
+    /// Another stage was pushed on top of this one
+    fn on_pause(&mut self, _world: &WorldState) {}
+    /// The stage above this one was popped
+    fn on_resume(&mut self, _world: &WorldState) {}
+    fn tick(&mut self, _world: &WorldState) -> Trans { Trans::None }
+    fn on_exit(&mut self, _world: &WorldState) {}
+    /// Despawn the entities created while this stage was active once it exits,
+    /// except those marked `Persistent`
+    fn cleanup_on_exit(&self) -> bool { false }
     fn dispatch(&mut self, _data: Box<dyn Any>) {}
-} 
+}
 
+#[derive(Default)]
 pub struct DefaultStage {}
 impl Stage for DefaultStage {}
 impl DefaultStage {
@@ -22,11 +51,15 @@ pub struct StageStoreProto {
 
 pub type StageStore = RefCell<StageStoreProto>;
 
+/// Entry of the world's stage stack, shared so hooks run without the stack borrowed
+pub type ActiveStage = (String, Rc<RefCell<Box<dyn Stage>>>);
+
 impl StageStoreProto {
     pub fn new() -> StageStore {
         RefCell::new(StageStoreProto { store: HashMap::new() })
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn borrow(&self) -> &HashMap<String, RefCell<Box<dyn Stage>>> {
         &self.store
     }
@@ -43,11 +76,149 @@ impl StageStoreProto {
         self.store.remove(name)
     }
 
-    pub fn get(&self, name: &str) -> Ref<Box<dyn Stage>> {
-        self.store.get(name).unwrap().borrow()
+    pub fn contains(&self, name: &str) -> bool {
+        self.store.contains_key(name)
+    }
+
+    /// Move the stage out of the store, to be put back with `save_stage`
+    pub fn take(&mut self, name: &str) -> Result<Box<dyn Stage>> {
+        self.store.remove(name).map(RefCell::into_inner).ok_or_else(|| Error::UnknownStage(name.to_string()))
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Ref<'_, Box<dyn Stage>>> {
+        self.store.get(name).ok_or_else(|| Error::UnknownStage(name.to_string()))?
+            .try_borrow().map_err(|_| Error::BorrowConflict("stage"))
+    }
+
+    pub fn try_get_mut(&self, name: &str) -> Result<RefMut<'_, Box<dyn Stage>>> {
+        self.store.get(name).ok_or_else(|| Error::UnknownStage(name.to_string()))?
+            .try_borrow_mut().map_err(|_| Error::BorrowConflict("stage"))
+    }
+
+    /// Panics if the stage is unknown or borrowed, see `try_get`
+    pub fn get(&self, name: &str) -> Ref<'_, Box<dyn Stage>> {
+        self.try_get(name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Panics if the stage is unknown or borrowed, see `try_get_mut`
+    pub fn get_mut(&self, name: &str) -> RefMut<'_, Box<dyn Stage>> {
+        self.try_get_mut(name).unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::World;
+
+    struct Logged {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+        trans: Option<Trans>,
+    }
+
+    impl Logged {
+        fn new(name: &'static str, log: &Rc<RefCell<Vec<String>>>, trans: Option<Trans>) -> Self {
+            Self { name, log: log.clone(), trans }
+        }
+
+        fn log(&self, event: &str) {
+            self.log.borrow_mut().push(format!("{} {}", self.name, event));
+        }
+    }
+
+    impl Stage for Logged {
+        fn on_enter(&mut self, world: &WorldState) {
+            assert_eq!(world.current_stage().as_deref(), Some(self.name));
+            self.log("enter")
+        }
+        fn on_pause(&mut self, _world: &WorldState) { self.log("pause") }
+        fn on_resume(&mut self, _world: &WorldState) { self.log("resume") }
+        fn on_exit(&mut self, _world: &WorldState) { self.log("exit") }
+        fn tick(&mut self, _world: &WorldState) -> Trans {
+            self.trans.take().unwrap_or(Trans::None)
+        }
+    }
+
+    #[test]
+    fn test_stack_transitions() {
+        let world = World::new();
+        let state = &world.state;
+        let log = Rc::new(RefCell::new(Vec::new()));
+        state.register_stage("menu", Logged::new("menu", &log, Some(Trans::Pop)));
+        state.enter("game", Logged::new("game", &log, Some(Trans::Push("menu".to_string())))).unwrap();
+
+        assert_eq!(state.push_stage("missing"), Err(Error::UnknownStage("missing".to_string())));
+        assert_eq!(state.switch_stage("missing"), Err(Error::UnknownStage("missing".to_string())));
+        assert_eq!(state.current_stage(), Some("game".to_string()));
+
+        state.tick(0.).unwrap();
+        assert_eq!(state.stage_stack(), vec!["game", "menu"]);
+        state.tick(0.).unwrap();
+        assert_eq!(state.current_stage(), Some("game".to_string()));
+        assert_eq!(*log.borrow(), vec!["game enter", "game pause", "menu enter", "menu exit", "game resume"]);
+
+        state.register_stage("pause", Logged::new("pause", &log, None));
+        state.push_stage("pause").unwrap();
+        log.borrow_mut().clear();
+        state.quit();
+        assert!(!state.is_running());
+        assert!(state.stage_stack().is_empty());
+        // Quitting exits every stage without resuming the ones below
+        assert_eq!(*log.borrow(), vec!["pause exit", "game exit"]);
+        // Popped stages go back to the store and can be entered again
+        state.push_stage("menu").unwrap();
+        assert!(state.is_running());
     }
 
-    pub fn get_mut(&self, name: &str) -> RefMut<Box<dyn Stage>> {
-        self.store.get(name).unwrap().borrow_mut()
+    struct Level;
+
+    impl Stage for Level {
+        fn cleanup_on_exit(&self) -> bool { true }
+    }
+
+    #[test]
+    fn test_cleanup_on_exit() {
+        let world = World::new();
+        let state = &world.state;
+        let kept = state.create_entity();
+        state.enter("level", Level).unwrap();
+        let prop = state.create_entity();
+        let player = state.create_entity();
+        state.bind_component(player, crate::ecs::Persistent);
+        assert_eq!(state.stage_entities("level"), vec![prop, player]);
+
+        state.switch_stage("default").unwrap();
+        assert!(state.is_alive(kept));
+        assert!(!state.is_alive(prop));
+        assert!(state.is_alive(player));
+    }
+
+    /// Dismisses itself as soon as it is entered
+    struct Popup;
+
+    impl Stage for Popup {
+        fn on_enter(&mut self, world: &WorldState) {
+            assert_eq!(world.push_stage("missing"), Err(Error::UnknownStage("missing".to_string())));
+            assert_eq!(world.pop_stage(), None);
+            assert_eq!(world.current_stage().as_deref(), Some("popup"));
+        }
+    }
+
+    #[test]
+    fn test_transitions_from_hooks() {
+        let world = World::new();
+        let state = &world.state;
+        let log = Rc::new(RefCell::new(Vec::new()));
+        state.enter("game", Logged::new("game", &log, None)).unwrap();
+        state.register_stage("popup", Popup);
+
+        state.push_stage("popup").unwrap();
+        assert_eq!(state.stage_stack(), vec!["game"]);
+        assert_eq!(*log.borrow(), vec!["game enter", "game pause", "game resume"]);
+        // The dismissed stage went back to the store
+        assert!(state.stage_store.borrow().contains("popup"));
+        assert_eq!(state.pop_stage(), Some("game".to_string()));
+        assert!(!state.is_running());
     }
 }
@@ -0,0 +1,199 @@
+use std::slice;
+use std::iter::Zip;
+
+use crate::ecs::entity::Entity;
+
+/// Sparse set keyed by entity handles
+///
+/// Components live in a contiguous `Vec` so iteration walks packed memory,
+/// the sparse array maps an entity index to its dense position. Removal swaps
+/// the last row into the hole so the dense arrays stay packed.
+#[derive(Clone)]
+pub struct SparseSet<C> {
+    sparse: Vec<Option<usize>>,
+    entities: Vec<Entity>,
+    data: Vec<C>,
+}
+
+impl<C> SparseSet<C> {
+    pub fn new() -> Self {
+        Self {
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            sparse: Vec::with_capacity(capacity),
+            entities: Vec::with_capacity(capacity),
+            data: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Dense position of the entity's row
+    #[inline]
+    pub fn dense_index(&self, entity: &Entity) -> Option<usize> {
+        SparseIndex { sparse: &self.sparse, entities: &self.entities }.dense_index(entity)
+    }
+
+    #[inline]
+    pub fn contains_key(&self, entity: &Entity) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    #[inline]
+    pub fn get(&self, entity: &Entity) -> Option<&C> {
+        self.dense_index(entity).map(|dense| &self.data[dense])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, entity: &Entity) -> Option<&mut C> {
+        match self.dense_index(entity) {
+            Some(dense) => Some(&mut self.data[dense]),
+            None => None,
+        }
+    }
+
+    /// Insert or replace the row of the entity, returning the replaced component
+    pub fn insert(&mut self, entity: Entity, component: C) -> Option<C> {
+        let slot = entity.index() as usize;
+        if slot >= self.sparse.len() {
+            self.sparse.resize(slot + 1, None);
+        }
+        if let Some(dense) = self.sparse[slot] {
+            // A row left by an older generation of the slot is overwritten as well
+            let replaced = std::mem::replace(&mut self.data[dense], component);
+            let stale = self.entities[dense] != entity;
+            self.entities[dense] = entity;
+            return if stale { None } else { Some(replaced) };
+        }
+        self.sparse[slot] = Some(self.data.len());
+        self.entities.push(entity);
+        self.data.push(component);
+        None
+    }
+
+    pub fn remove(&mut self, entity: &Entity) -> Option<C> {
+        let dense = self.dense_index(entity)?;
+        self.sparse[entity.index() as usize] = None;
+        self.entities.swap_remove(dense);
+        let component = self.data.swap_remove(dense);
+        if let Some(moved) = self.entities.get(dense) {
+            self.sparse[moved.index() as usize] = Some(dense);
+        }
+        Some(component)
+    }
+
+    pub fn clear(&mut self) {
+        self.sparse.clear();
+        self.entities.clear();
+        self.data.clear();
+    }
+
+    /// Entities in dense order
+    #[inline]
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Components in dense order, matching `entities`
+    #[inline]
+    pub fn as_slice(&self) -> &[C] {
+        &self.data
+    }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [C] {
+        &mut self.data
+    }
+
+    /// Split into a read only index and the mutable component rows
+    #[inline]
+    pub fn split_mut(&mut self) -> (SparseIndex<'_>, &mut [C]) {
+        (SparseIndex { sparse: &self.sparse, entities: &self.entities }, &mut self.data)
+    }
+
+    pub fn keys(&self) -> slice::Iter<'_, Entity> {
+        self.entities.iter()
+    }
+
+    pub fn values(&self) -> slice::Iter<'_, C> {
+        self.data.iter()
+    }
+
+    pub fn values_mut(&mut self) -> slice::IterMut<'_, C> {
+        self.data.iter_mut()
+    }
+
+    pub fn iter(&self) -> Zip<slice::Iter<'_, Entity>, slice::Iter<'_, C>> {
+        self.entities.iter().zip(self.data.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> Zip<slice::Iter<'_, Entity>, slice::IterMut<'_, C>> {
+        self.entities.iter().zip(self.data.iter_mut())
+    }
+}
+
+impl<C> Default for SparseSet<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entity to dense position lookup borrowed from a `SparseSet`
+#[derive(Clone, Copy)]
+pub struct SparseIndex<'a> {
+    sparse: &'a [Option<usize>],
+    entities: &'a [Entity],
+}
+
+impl<'a> SparseIndex<'a> {
+    #[inline]
+    pub fn dense_index(&self, entity: &Entity) -> Option<usize> {
+        match self.sparse.get(entity.index() as usize) {
+            Some(Some(dense)) if self.entities[*dense] == *entity => Some(*dense),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_remove_keeps_index() {
+        let mut set = SparseSet::new();
+        let a = Entity::new(0, 0);
+        let b = Entity::new(5, 0);
+        let c = Entity::new(2, 1);
+        set.insert(a, "a");
+        set.insert(b, "b");
+        set.insert(c, "c");
+        assert_eq!(set.insert(b, "bb"), Some("b"));
+
+        assert_eq!(set.remove(&a), Some("a"));
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.get(&c), Some(&"c"));
+        assert_eq!(set.get(&b), Some(&"bb"));
+        assert!(set.get(&a).is_none());
+
+        // Same slot, older generation
+        let stale = Entity::new(2, 0);
+        assert!(set.get(&stale).is_none());
+        assert!(set.remove(&stale).is_none());
+        assert_eq!(set.entities().len(), set.as_slice().len());
+    }
+}
@@ -1,57 +1,425 @@
-use std::cell::{Ref, RefMut, RefCell};
+use std::cell::{Cell, Ref, RefMut, RefCell};
 use std::any::Any;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::error::{Error, Result};
+use crate::ecs::world::WorldState;
+use crate::ecs::time::Time;
+use crate::ecs::commands::Commands;
+
+/// World access handed to a system while it ticks
+///
+/// Systems hold no handle to the world themselves, so they can be built before it exists.
+pub struct SystemContext<'w> {
+    pub world: &'w WorldState,
+    /// Frame timing, carrying the fixed step for `SystemStage::FixedUpdate` systems
+    pub time: Time,
+}
+
+impl<'w> SystemContext<'w> {
+    /// Record structural edits, applied once the system returns
+    pub fn commands(&self) -> Commands<'w> {
+        self.world.commands()
+    }
+}
 
 pub trait System {
-    fn tick(&mut self) {}
+    fn tick(&mut self, _ctx: &SystemContext) {}
     fn dispatch(&mut self, _data: Box<dyn Any>) {}
 }
 
-/*
-pub trait RendererSystem {
-    fn render_tick(&self) {
+/// Phases of a world tick, systems of a stage run before the next stage starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SystemStage {
+    PreUpdate,
+    /// Ticked zero or more times per frame with a constant `Time::fixed_delta`
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    /// Driven by `WorldState::render_tick` instead of `WorldState::tick`
+    Render,
+}
+
+pub type RunCriteria = Box<dyn Fn(&WorldState) -> bool>;
+
+/// Registration options of a system
+///
+/// ```ignore
+/// world.add_system(
+///     SystemDescriptor::new("movement", movement)
+///         .after("input")
+///         .run_if(|world| world.active_camera.get().is_some())
+/// )?;
+/// ```
+pub struct SystemDescriptor {
+    name: String,
+    system: Box<dyn System>,
+    stage: SystemStage,
+    labels: Vec<String>,
+    before: Vec<String>,
+    after: Vec<String>,
+    run_criteria: Option<RunCriteria>,
+    enabled: bool,
+}
+
+impl SystemDescriptor {
+    pub fn new<S: 'static + System>(name: &str, system: S) -> Self {
+        Self {
+            name: name.to_string(),
+            system: Box::new(system),
+            stage: SystemStage::Update,
+            labels: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            run_criteria: None,
+            enabled: true,
+        }
+    }
+
+    /// Run in the stage instead of `SystemStage::Update`
+    pub fn in_stage(mut self, stage: SystemStage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    /// Extra name other systems can order against, shared labels group systems
+    pub fn label(mut self, label: &str) -> Self {
+        self.labels.push(label.to_string());
+        self
     }
 
-    fn dispatch(&mut self, data: Box<dyn Any>) {}
+    /// Run before the system or label in the same stage
+    pub fn before(mut self, target: &str) -> Self {
+        self.before.push(target.to_string());
+        self
+    }
+
+    /// Run after the system or label in the same stage
+    pub fn after(mut self, target: &str) -> Self {
+        self.after.push(target.to_string());
+        self
+    }
+
+    /// Skip the tick whenever the criteria returns false
+    pub fn run_if<F: 'static + Fn(&WorldState) -> bool>(mut self, criteria: F) -> Self {
+        self.run_criteria = Some(Box::new(criteria));
+        self
+    }
+
+    /// Register the system disabled
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+}
+
+struct SystemEntry {
+    name: String,
+    system: RefCell<Box<dyn System>>,
+    stage: SystemStage,
+    labels: Vec<String>,
+    before: Vec<String>,
+    after: Vec<String>,
+    run_criteria: Option<RunCriteria>,
+    enabled: Cell<bool>,
+    // Change tick of the previous run, see `WorldState::last_run`
+    last_run: Cell<u64>,
+}
+
+impl SystemEntry {
+    fn matches(&self, target: &str) -> bool {
+        self.name == target || self.labels.iter().any(|label| label == target)
+    }
 }
-*/
 
 pub struct SystemStoreProto {
-    store: BTreeMap<String, RefCell<Box<dyn System>>>,
+    // Registration order, used to break ordering ties
+    store: Vec<SystemEntry>,
+    schedule: BTreeMap<SystemStage, Vec<usize>>,
 }
 
 pub type SystemStore = RefCell<SystemStoreProto>;
 
 impl SystemStoreProto {
     pub fn new() -> SystemStore {
-        RefCell::new(SystemStoreProto { store: BTreeMap::new() })
+        RefCell::new(SystemStoreProto { store: Vec::new(), schedule: BTreeMap::new() })
+    }
+
+    /// Register the system in `SystemStage::Update`
+    pub fn register<S: 'static + System>(&mut self, name: &str, system: S) -> Result<()> {
+        self.add(SystemDescriptor::new(name, system))
+    }
+
+    /// Register a system, rejecting duplicate names and ordering cycles
+    pub fn add(&mut self, descriptor: SystemDescriptor) -> Result<()> {
+        if self.contains(&descriptor.name) {
+            return Err(Error::DuplicateSystem(descriptor.name));
+        }
+        let name = descriptor.name.clone();
+        self.store.push(SystemEntry {
+            name: descriptor.name,
+            system: RefCell::new(descriptor.system),
+            stage: descriptor.stage,
+            labels: descriptor.labels,
+            before: descriptor.before,
+            after: descriptor.after,
+            run_criteria: descriptor.run_criteria,
+            enabled: Cell::new(descriptor.enabled),
+            last_run: Cell::new(0),
+        });
+        if self.rebuild_schedule().is_err() {
+            // Report the newcomer rather than whichever node the sort got stuck on
+            self.store.pop();
+            self.rebuild_schedule()?;
+            return Err(Error::SystemCycle(name));
+        }
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Option<Box<dyn System>> {
+        let index = self.position(name)?;
+        let entry = self.store.remove(index);
+        // Removing a node never introduces a cycle
+        let _ = self.rebuild_schedule();
+        Some(entry.system.into_inner())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    /// Registered system names in registration order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.store.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// System names of the stage in running order
+    pub fn schedule(&self, stage: SystemStage) -> Vec<&str> {
+        self.schedule.get(&stage)
+            .map(|order| order.iter().map(|index| self.store[*index].name.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Change tick every system last ran at, see `WorldState::last_run`
+    pub fn last_runs(&self) -> Vec<(String, u64)> {
+        self.store.iter().map(|entry| (entry.name.clone(), entry.last_run.get())).collect()
+    }
+
+    pub fn set_last_run(&self, name: &str, tick: u64) -> Result<()> {
+        self.entry(name)?.last_run.set(tick);
+        Ok(())
+    }
+
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        self.entry(name)?.enabled.set(enabled);
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, name: &str) -> Result<bool> {
+        Ok(self.entry(name)?.enabled.get())
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Ref<'_, Box<dyn System>>> {
+        self.entry(name)?.system.try_borrow().map_err(|_| Error::BorrowConflict("system"))
     }
 
-    pub fn borrow(&self) -> &BTreeMap<String, RefCell<Box<dyn System>>> {
-        &self.store
+    pub fn try_get_mut(&self, name: &str) -> Result<RefMut<'_, Box<dyn System>>> {
+        self.entry(name)?.system.try_borrow_mut().map_err(|_| Error::BorrowConflict("system"))
     }
 
-    pub fn register<S: 'static + System>(&mut self, name: &str, system: S) {
-        if !self.store.contains_key(name) {
-            self.store.insert(name.to_string(), RefCell::new(Box::new(system)));
+    /// Panics if the system is unknown or borrowed, see `try_get`
+    pub fn get(&self, name: &str) -> Ref<'_, Box<dyn System>> {
+        self.try_get(name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Panics if the system is unknown or borrowed, see `try_get_mut`
+    pub fn get_mut(&self, name: &str) -> RefMut<'_, Box<dyn System>> {
+        self.try_get_mut(name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Tick the enabled systems of the stage whose run criteria pass
+    pub fn run(&self, stage: SystemStage, state: &WorldState, time: &Time) {
+        if let Some(order) = self.schedule.get(&stage) {
+            for index in order {
+                let entry = &self.store[*index];
+                if !entry.enabled.get() {
+                    continue;
+                }
+                if let Some(criteria) = &entry.run_criteria {
+                    if !criteria(state) {
+                        continue;
+                    }
+                }
+                let tick = state.increment_change_tick();
+                let outside = state.replace_last_run(entry.last_run.get());
+                let ctx = SystemContext { world: state, time: *time };
+                entry.system.borrow_mut().as_mut().tick(&ctx);
+                entry.last_run.set(tick);
+                state.replace_last_run(outside);
+                state.apply_commands();
+            }
         }
     }
 
-    pub fn unregister(&mut self, name: &str) {
-        self.store.remove(name);
+    fn position(&self, name: &str) -> Option<usize> {
+        self.store.iter().position(|entry| entry.name == name)
+    }
+
+    fn entry(&self, name: &str) -> Result<&SystemEntry> {
+        self.store.iter().find(|entry| entry.name == name)
+            .ok_or_else(|| Error::UnknownSystem(name.to_string()))
     }
 
-    pub fn get(&self, name: &str) -> Ref<Box<dyn System>> {
-        self.store.get(name).unwrap().borrow()
+    /// Topologically sort every stage, ties keep the registration order
+    fn rebuild_schedule(&mut self) -> Result<()> {
+        let mut schedule = BTreeMap::new();
+        let mut stages: Vec<SystemStage> = self.store.iter().map(|entry| entry.stage).collect();
+        stages.sort();
+        stages.dedup();
+        for stage in stages {
+            let nodes: Vec<usize> = (0..self.store.len()).filter(|i| self.store[*i].stage == stage).collect();
+            let mut edges: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+            for &node in &nodes {
+                let entry = &self.store[node];
+                for &other in nodes.iter().filter(|other| **other != node) {
+                    if entry.before.iter().any(|target| self.store[other].matches(target)) {
+                        edges.entry(node).or_default().insert(other);
+                    }
+                    if entry.after.iter().any(|target| self.store[other].matches(target)) {
+                        edges.entry(other).or_default().insert(node);
+                    }
+                }
+            }
+
+            let mut incoming: BTreeMap<usize, usize> = nodes.iter().map(|node| (*node, 0)).collect();
+            for targets in edges.values() {
+                for target in targets {
+                    *incoming.get_mut(target).unwrap() += 1;
+                }
+            }
+            let mut ready: BTreeSet<usize> = incoming.iter().filter(|(_, count)| **count == 0).map(|(node, _)| *node).collect();
+            let mut order = Vec::with_capacity(nodes.len());
+            while let Some(node) = ready.iter().next().cloned() {
+                ready.remove(&node);
+                order.push(node);
+                for target in edges.get(&node).into_iter().flatten() {
+                    let count = incoming.get_mut(target).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.insert(*target);
+                    }
+                }
+            }
+            if order.len() < nodes.len() {
+                let stuck = nodes.iter().find(|node| !order.contains(node)).unwrap();
+                return Err(Error::SystemCycle(self.store[*stuck].name.clone()));
+            }
+            schedule.insert(stage, order);
+        }
+        self.schedule = schedule;
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
 
-    pub fn get_mut(&self, name: &str) -> RefMut<Box<dyn System>> {
-        self.store.get(name).unwrap().borrow_mut()
+    struct Record {
+        name: &'static str,
+        log: Rc<RefCell<Vec<&'static str>>>,
     }
 
-    pub fn tick(&self) {
-        for system in self.store.values() {
-            system.borrow_mut().as_mut().tick()
+    impl System for Record {
+        fn tick(&mut self, _ctx: &SystemContext) {
+            self.log.borrow_mut().push(self.name);
         }
     }
+
+    #[test]
+    fn test_schedule_order_and_criteria() {
+        let state = WorldState::new();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let record = |name| Record { name, log: log.clone() };
+
+        state.add_system(SystemDescriptor::new("render", record("render")).in_stage(SystemStage::Render)).unwrap();
+        state.add_system(SystemDescriptor::new("movement", record("movement")).after("input").label("physics")).unwrap();
+        state.add_system(SystemDescriptor::new("input", record("input"))).unwrap();
+        state.add_system(SystemDescriptor::new("collision", record("collision")).after("physics")).unwrap();
+        state.add_system(SystemDescriptor::new("cleanup", record("cleanup")).in_stage(SystemStage::PostUpdate)).unwrap();
+        state.add_system(SystemDescriptor::new("never", record("never")).run_if(|_| false)).unwrap();
+        state.add_system(SystemDescriptor::new("early", record("early")).in_stage(SystemStage::PreUpdate)).unwrap();
+        state.add_system(SystemDescriptor::new("physics", record("fixed")).in_stage(SystemStage::FixedUpdate)).unwrap();
+
+        assert_eq!(
+            state.register_system("input", record("input")),
+            Err(Error::DuplicateSystem("input".to_string()))
+        );
+        assert_eq!(
+            state.add_system(SystemDescriptor::new("loop", record("loop")).before("input").after("collision")),
+            Err(Error::SystemCycle("loop".to_string()))
+        );
+
+        state.time_mut().set_fixed_delta(0.125);
+        state.tick(0.25).unwrap();
+        assert_eq!(*log.borrow(), vec!["early", "fixed", "fixed", "input", "movement", "collision", "cleanup"]);
+
+        log.borrow_mut().clear();
+        state.set_system_enabled("movement", false).unwrap();
+        state.tick(0.0625).unwrap();
+        state.render_tick();
+        assert_eq!(*log.borrow(), vec!["early", "input", "collision", "cleanup", "render"]);
+        assert!(state.set_system_enabled("missing", true).is_err());
+    }
+
+    /// Stateless system seeing the world only through its context
+    struct Elapsed;
+
+    impl System for Elapsed {
+        fn tick(&mut self, ctx: &SystemContext) {
+            let elapsed = ctx.world.resource::<f32>().map_or(0., |elapsed| *elapsed);
+            ctx.world.insert_resource(elapsed + ctx.time.delta());
+        }
+    }
+
+    #[test]
+    fn test_context_instead_of_world_handle() {
+        let world = crate::ecs::World::new();
+        world.state.register_system("elapsed", Elapsed).unwrap();
+        world.state.tick(0.25).unwrap();
+        world.state.tick(0.125).unwrap();
+        assert_eq!(*world.state.resource::<f32>().unwrap(), 0.375);
+        // No system keeps a clone of the world alive
+        assert_eq!(Rc::strong_count(&world.state), 1);
+    }
+
+    /// Tries to change the registrations while the schedule runs
+    struct Registrar {
+        results: Rc<RefCell<Vec<Result<()>>>>,
+    }
+
+    impl System for Registrar {
+        fn tick(&mut self, ctx: &SystemContext) {
+            let mut results = self.results.borrow_mut();
+            results.push(ctx.world.register_system("late", Elapsed));
+            results.push(ctx.world.unregister_system("elapsed"));
+            results.push(ctx.world.set_system_enabled("elapsed", false));
+        }
+    }
+
+    #[test]
+    fn test_registration_while_running() {
+        let state = WorldState::new();
+        let results = Rc::new(RefCell::new(Vec::new()));
+        state.register_system("registrar", Registrar { results: results.clone() }).unwrap();
+        state.register_system("elapsed", Elapsed).unwrap();
+        state.tick(0.5).unwrap();
+
+        let conflict = Err(Error::BorrowConflict("system store"));
+        assert_eq!(*results.borrow(), vec![conflict.clone(), conflict, Ok(())]);
+        assert!(state.resource::<f32>().is_none());
+        assert_eq!(state.unregister_system("registrar"), Ok(()));
+        assert_eq!(state.unregister_system("registrar"), Err(Error::UnknownSystem("registrar".to_string())));
+    }
 }
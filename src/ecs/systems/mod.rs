@@ -1,2 +1,5 @@
 pub mod rendering;
+pub mod raster;
+pub mod svg;
 pub mod movement;
+pub mod transform;
@@ -0,0 +1,125 @@
+use crate::core::*;
+use crate::ecs::*;
+
+/// Software backend painting the `DrawList` into the `Framebuffer` resource
+///
+/// Register it after `RenderingSystem` so it sees the list of the same frame:
+///
+/// ```ignore
+/// world.register_renderer("draw_list", RenderingSystem::new())?;
+/// world.register_renderer("raster", RasterSystem::new())?;
+/// world.render_tick();
+/// let png = world.resource::<Framebuffer>().unwrap().to_png();
+/// ```
+///
+/// Text is not rasterized, framed text only draws its frame.
+pub struct RasterSystem {
+    pub background: Rgba,
+    /// Used where a primitive leaves its stroke to the backend
+    pub stroke: Rgba,
+}
+
+impl RasterSystem {
+    pub fn new() -> Self {
+        Self {
+            background: [0, 0, 0, 255],
+            stroke: [255, 255, 255, 255],
+        }
+    }
+
+    fn color(&self, name: &Option<String>) -> Rgba {
+        name.as_ref().and_then(|name| parse_color(name)).unwrap_or(self.stroke)
+    }
+
+    fn draw(&self, image: &mut Framebuffer, item: &DrawItem) {
+        let depth = item.depth;
+        match &item.primitive {
+            Primitive::Line { from, to, stroke } => image.draw_line(*from, *to, self.color(stroke)),
+            Primitive::Triangle { points, fill } => image.fill_polygon(points, self.color(fill)),
+            Primitive::Polygon { points, fill, stroke } => {
+                let points: Vec<Point3<f32>> = points.iter().map(|point| Point3::new(point.x, point.y, depth)).collect();
+                if fill.is_some() {
+                    image.fill_polygon(&points, self.color(fill));
+                }
+                if stroke.is_some() || fill.is_none() {
+                    image.stroke_polygon(&points, self.color(stroke));
+                }
+            }
+            Primitive::Circle { center, radius, fill, stroke } => {
+                if fill.is_some() {
+                    image.fill_circle(*center, *radius, depth, self.color(fill));
+                }
+                if stroke.is_some() || fill.is_none() {
+                    image.stroke_circle(*center, *radius, depth, self.color(stroke));
+                }
+            }
+            Primitive::Text { .. } => {}
+        }
+    }
+}
+
+impl Default for RasterSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl System for RasterSystem {
+    fn tick(&mut self, ctx: &SystemContext) {
+        let world = ctx.world;
+        let (width, height) = match world.resource::<DrawList>() {
+            Some(list) => (list.viewport.width.max(0.) as usize, list.viewport.height.max(0.) as usize),
+            None => return,
+        };
+        let resized = world.resource::<Framebuffer>()
+            .map_or(true, |image| (image.width(), image.height()) != (width, height));
+        if resized {
+            world.insert_resource(Framebuffer::new(width, height));
+        }
+
+        let list = world.resource::<DrawList>().unwrap();
+        let mut image = world.resource_mut::<Framebuffer>().unwrap();
+        image.clear(self.background);
+        for item in list.items() {
+            self.draw(&mut image, item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raster_scene() {
+        let world = World::new();
+        let state = &world.state;
+        let camera = state.create_entity();
+        let mut transform = TransformComponent::default();
+        transform.set_translation_xyz(0., 0., 10.);
+        state.bind_component(camera, Camera::Perspective {
+            projection: Perspective3::new(1., std::f32::consts::FRAC_PI_2, 1., 100.),
+        });
+        state.bind_component(camera, transform);
+        state.switch_camera(camera);
+        state.insert_resource(Viewport::new(40., 40.));
+        state.register_renderer("draw_list", RenderingSystem::new()).unwrap();
+        state.register_renderer("raster", RasterSystem::new()).unwrap();
+
+        // A red quad in front of a larger blue one, both covering the view center
+        let quad = |z: f32, half: f32, color: &str| SimpleMesh::new(
+            vec![Point3::new(-half, -half, z), Point3::new(half, -half, z), Point3::new(half, half, z), Point3::new(-half, half, z)],
+            vec![(0, 1, 2, color.to_string()), (0, 2, 3, color.to_string())],
+        );
+        state.spawn((quad(0., 2., "red"), TransformComponent::default()));
+        state.spawn((quad(-5., 6., "blue"), TransformComponent::default()));
+        state.render_tick();
+
+        let image = state.resource::<Framebuffer>().unwrap();
+        assert_eq!((image.width(), image.height()), (40, 40));
+        assert_eq!(image.pixel(20, 20), Some([255, 0, 0, 255]));
+        // The near quad spans 16..24 on screen, the far one 12..28
+        assert_eq!(image.pixel(20, 13), Some([0, 0, 255, 255]));
+        assert_eq!(image.pixel(0, 0), Some([0, 0, 0, 255]));
+    }
+}
@@ -1,20 +1,361 @@
-use std::rc::Rc;
+use crate::core::*;
 use crate::ecs::*;
 
-pub struct RenderingSystem {
-    state: Rc<WorldState>
-}
+/// Projects meshes and widgets through the active camera into the `DrawList` resource
+///
+/// Register it with `WorldState::register_renderer`, backends read the list after
+/// `render_tick`. The surface size comes from the `Viewport` resource.
+#[derive(Default)]
+pub struct RenderingSystem {}
 
 impl RenderingSystem {
-    pub fn new(state: Rc<WorldState>) -> Self {
-        Self {
-            state
-        }
+    pub fn new() -> Self {
+        Self {}
     }
 }
 
 impl System for RenderingSystem {
-    fn tick(&mut self) {
+    fn tick(&mut self, ctx: &SystemContext) {
+        let world = ctx.world;
+        let viewport = world.resource::<Viewport>().map(|viewport| *viewport).unwrap_or_default();
+        let mut list = DrawList::new(viewport);
+        if let Some(projector) = Projector::active(world, viewport) {
+            draw_meshes(world, &projector, &mut list);
+            draw_widgets(world, &projector, &mut list);
+        }
+        list.sort();
+        match world.resource_mut::<DrawList>() {
+            Some(mut current) => *current = list,
+            None => { world.insert_resource(list); }
+        }
     }
 }
 
+/// Signed distances of a clip space point to the near and far planes, negative outside
+const DEPTH_PLANES: [fn(&Vector4<f32>) -> f32; 2] = [
+    |clip| clip.w + clip.z,
+    |clip| clip.w - clip.z,
+];
+
+/// Model to screen mapping of the active camera
+struct Projector {
+    camera: CameraView,
+    viewport: Viewport,
+}
+
+impl Projector {
+    fn active(world: &WorldState, viewport: Viewport) -> Option<Self> {
+        let camera = world.get_active_camera().ok()?;
+        Some(Self { camera, viewport })
+    }
+
+    /// Screen position with the depth in z of a view space point, `None` outside the depth range or behind the camera
+    fn project_view(&self, point: &Point3<f32>) -> Option<Point3<f32>> {
+        self.camera.view_to_screen(point, &self.viewport)
+    }
+
+    fn to_view(&self, model: &Matrix4<f32>, point: &Point3<f32>) -> Point3<f32> {
+        (self.camera.view * model).transform_point(point)
+    }
+
+    fn project(&self, model: &Matrix4<f32>, point: &Point3<f32>) -> Option<Point3<f32>> {
+        self.project_view(&self.to_view(model, point))
+    }
+
+    fn to_clip(&self, model: &Matrix4<f32>, point: &Point3<f32>) -> Vector4<f32> {
+        self.camera.view_projection * model * point.to_homogeneous()
+    }
+
+    /// Screen position with the depth in z of a clip space point inside the depth range
+    fn clip_to_screen(&self, clip: &Vector4<f32>) -> Point3<f32> {
+        let ndc = clip.xyz() / clip.w;
+        let screen = self.viewport.to_screen(ndc.x, ndc.y);
+        Point3::new(screen.x, screen.y, ndc.z)
+    }
+
+    /// Segment cut to the near and far planes, `None` if it lies entirely outside
+    fn project_line(&self, model: &Matrix4<f32>, from: &Point3<f32>, to: &Point3<f32>) -> Option<(Point3<f32>, Point3<f32>)> {
+        let (mut from, mut to) = (self.to_clip(model, from), self.to_clip(model, to));
+        for distance in DEPTH_PLANES.iter() {
+            let (a, b) = (distance(&from), distance(&to));
+            if (a < 0. && b < 0.) || a.is_nan() || b.is_nan() {
+                return None;
+            }
+            if a < 0. {
+                from += (to - from) * (a / (a - b));
+            } else if b < 0. {
+                to += (from - to) * (b / (b - a));
+            }
+        }
+        Some((self.clip_to_screen(&from), self.clip_to_screen(&to)))
+    }
+
+    /// Polygon cut to the near and far planes (Sutherland–Hodgman) with its mean depth,
+    /// `None` if less than a triangle remains
+    fn project_polygon<'a, I>(&self, model: &Matrix4<f32>, points: I) -> Option<(Vec<Point3<f32>>, f32)>
+        where I: IntoIterator<Item = &'a Point3<f32>>
+    {
+        let mut clipped: Vec<Vector4<f32>> = points.into_iter().map(|point| self.to_clip(model, point)).collect();
+        for distance in DEPTH_PLANES.iter() {
+            let mut inside = Vec::with_capacity(clipped.len() + 1);
+            for (i, a) in clipped.iter().enumerate() {
+                let b = clipped[(i + 1) % clipped.len()];
+                let (da, db) = (distance(a), distance(&b));
+                if da.is_nan() || db.is_nan() {
+                    return None;
+                }
+                if da >= 0. {
+                    inside.push(*a);
+                }
+                if (da >= 0.) != (db >= 0.) {
+                    inside.push(a + (b - a) * (da / (da - db)));
+                }
+            }
+            clipped = inside;
+        }
+        if clipped.len() < 3 {
+            return None;
+        }
+        let projected: Vec<Point3<f32>> = clipped.iter().map(|clip| self.clip_to_screen(clip)).collect();
+        let depth = projected.iter().map(|point| point.z).sum::<f32>() / projected.len() as f32;
+        Some((projected, depth))
+    }
+
+    /// Screen center and radius of a sphere, the radius is measured facing the camera
+    fn project_sphere(&self, model: &Matrix4<f32>, center: &Point3<f32>, radius: f32) -> Option<(Point2<f32>, f32, f32)> {
+        let view_center = self.to_view(model, center);
+        let screen = self.project_view(&view_center)?;
+        let edge = self.project_view(&(view_center + Vector3::new(0., radius, 0.)))?;
+        Some((screen.xy(), (edge.xy() - screen.xy()).norm(), screen.z))
+    }
+}
+
+/// Fill and stroke enabled by a brush action, 1 fills, 2 strokes and 3 does both
+fn brush_colors(action: u8, fill: &Option<String>, stroke: &Option<String>) -> (Option<String>, Option<String>) {
+    let fill = if action & 1 != 0 { fill.clone() } else { None };
+    let stroke = if action & 2 != 0 { stroke.clone() } else { None };
+    (fill, stroke)
+}
+
+fn push_line(projector: &Projector, list: &mut DrawList, model: &Matrix4<f32>, from: &Point3<f32>, to: &Point3<f32>, stroke: &Option<String>) {
+    if let Some((from, to)) = projector.project_line(model, from, to) {
+        list.push((from.z + to.z) / 2., Primitive::Line { from, to, stroke: stroke.clone() });
+    }
+}
+
+fn draw_meshes(world: &WorldState, projector: &Projector, list: &mut DrawList) {
+    let mut query = match world.query_filtered::<&MeshComponent, With<TransformComponent>>() {
+        Ok(query) => query,
+        Err(_) => return,
+    };
+    for (entity, mesh) in query.iter() {
+        let model = world.world_matrix(entity);
+        match mesh.cook() {
+            MeshRecipe::Basic { data } => draw_basic(projector, list, &model, data),
+            MeshRecipe::Simple { data } => draw_simple(projector, list, &model, data),
+            MeshRecipe::Complex { data } => draw_complex(projector, list, &model, data),
+        }
+    }
+}
+
+/// Consecutive vertices are joined unless the first index is listed in `breaks`
+fn draw_basic(projector: &Projector, list: &mut DrawList, model: &Matrix4<f32>, mesh: &BasicMesh) {
+    for (index, pair) in mesh.vertices.windows(2).enumerate() {
+        if !mesh.breaks.contains(&index) {
+            push_line(projector, list, model, &pair[0], &pair[1], &None);
+        }
+    }
+}
+
+fn draw_simple(projector: &Projector, list: &mut DrawList, model: &Matrix4<f32>, mesh: &SimpleMesh) {
+    for (a, b, c, color) in mesh.polygons.iter() {
+        let corners = [mesh.vertices.get(*a), mesh.vertices.get(*b), mesh.vertices.get(*c)];
+        if corners.iter().any(Option::is_none) {
+            continue;
+        }
+        // A triangle cut by a plane becomes a polygon, split again as a fan
+        if let Some((points, _)) = projector.project_polygon(model, corners.iter().flatten().copied()) {
+            for i in 1..points.len() - 1 {
+                let triangle = [points[0], points[i], points[i + 1]];
+                let depth = triangle.iter().map(|point| point.z).sum::<f32>() / 3.;
+                list.push(depth, Primitive::Triangle { points: triangle, fill: Some(color.clone()) });
+            }
+        }
+    }
+}
+
+fn draw_complex(projector: &Projector, list: &mut DrawList, model: &Matrix4<f32>, mesh: &ComplexMesh) {
+    for brush in mesh.brushes.iter() {
+        match brush {
+            Brush::Lines { stroke, fill, vertices, action } => {
+                let (fill, stroke) = brush_colors(*action, fill, stroke);
+                if fill.is_some() {
+                    if let Some((points, depth)) = projector.project_polygon(model, vertices.iter()) {
+                        let points = points.iter().map(|point| point.xy()).collect();
+                        list.push(depth, Primitive::Polygon { points, fill, stroke });
+                    }
+                } else {
+                    for pair in vertices.windows(2) {
+                        push_line(projector, list, model, &pair[0], &pair[1], &stroke);
+                    }
+                }
+            }
+            Brush::Circle { stroke, fill, center, radius, action } |
+            Brush::Sphere { stroke, fill, center, radius, action } => {
+                let (fill, stroke) = brush_colors(*action, fill, stroke);
+                if let Some((center, radius, depth)) = projector.project_sphere(model, center, *radius) {
+                    list.push(depth, Primitive::Circle { center, radius, fill, stroke });
+                }
+            }
+            Brush::Cube { stroke, center, size } => {
+                let half = size / 2.;
+                let corner = |i: usize| center + Vector3::new(
+                    if i & 1 == 0 { -half } else { half },
+                    if i & 2 == 0 { -half } else { half },
+                    if i & 4 == 0 { -half } else { half },
+                );
+                // Edges join corners that differ in exactly one axis bit
+                for from in 0..8 {
+                    for bit in [1, 2, 4].iter() {
+                        if from & bit == 0 {
+                            push_line(projector, list, model, &corner(from), &corner(from | bit), stroke);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn draw_widgets(world: &WorldState, projector: &Projector, list: &mut DrawList) {
+    let mut query = match world.query::<&WidgetComponent>() {
+        Ok(query) => query,
+        Err(_) => return,
+    };
+    for (entity, widget) in query.iter() {
+        let model = world.world_matrix(entity);
+        match widget {
+            Widget::Text { translation, text } => {
+                if let Some(screen) = projector.project(&model, &Point3::from(*translation)) {
+                    list.push(screen.z, Primitive::Text { position: screen.xy(), text: text.clone() });
+                }
+            }
+            Widget::FramedText { translation, text, width, height } => {
+                if let Some(screen) = projector.project(&model, &Point3::from(*translation)) {
+                    let (position, depth) = (screen.xy(), screen.z);
+                    let (x, y) = (width / 2., height / 2.);
+                    let points = vec![
+                        position + Vector2::new(-x, -y),
+                        position + Vector2::new(x, -y),
+                        position + Vector2::new(x, y),
+                        position + Vector2::new(-x, y),
+                    ];
+                    list.push(depth, Primitive::Polygon { points, fill: None, stroke: None });
+                    list.push(depth, Primitive::Text { position, text: text.clone() });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_world() -> World {
+        let world = World::new();
+        let state = &world.state;
+        let camera = state.create_entity();
+        let mut transform = TransformComponent::default();
+        transform.set_translation_xyz(0., 0., 10.);
+        state.bind_component(camera, Camera::Perspective {
+            projection: Perspective3::new(1., std::f32::consts::FRAC_PI_2, 1., 100.),
+        });
+        state.bind_component(camera, transform);
+        state.switch_camera(camera);
+        state.insert_resource(Viewport::new(200., 100.));
+        state.register_renderer("draw_list", RenderingSystem::new()).unwrap();
+        world
+    }
+
+    #[test]
+    fn test_draw_list_projection() {
+        let world = camera_world();
+        let state = &world.state;
+        let near = state.create_entity();
+        state.bind_component(near, SimpleMesh::new(
+            vec![Point3::new(0., 0., 0.), Point3::new(1., 0., 0.), Point3::new(0., 1., 0.)],
+            vec![(0, 1, 2, "red".to_string())],
+        ));
+        state.bind_component(near, TransformComponent::default());
+        let far = state.create_entity();
+        state.bind_component(far, BasicMesh::new(vec![Point3::new(-1., 0., 0.), Point3::new(1., 0., 0.)], vec![]));
+        let mut transform = TransformComponent::default();
+        transform.set_translation_xyz(0., 0., -10.);
+        state.bind_component(far, transform);
+        let behind = state.create_entity();
+        state.bind_component(behind, BasicMesh::new(vec![Point3::new(0., 0., 20.), Point3::new(1., 0., 20.)], vec![]));
+        state.bind_component(behind, TransformComponent::default());
+
+        state.render_tick();
+        let list = state.resource::<DrawList>().unwrap();
+        assert_eq!(list.len(), 2);
+        // The far line is painted first and spans a tenth of the view width at twice the distance
+        match &list.items()[0].primitive {
+            Primitive::Line { from, to, .. } => {
+                assert!((from.x - 95.).abs() < 1e-3 && (to.x - 105.).abs() < 1e-3);
+                assert!((from.y - 50.).abs() < 1e-3);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &list.items()[1].primitive {
+            Primitive::Triangle { points, fill } => {
+                assert_eq!(fill.as_deref(), Some("red"));
+                assert!((points[0].x - 100.).abs() < 1e-3 && (points[1].x - 110.).abs() < 1e-3);
+                assert!((points[2].y - 45.).abs() < 1e-3);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_near_plane_clipping() {
+        let world = camera_world();
+        let state = &world.state;
+        // Both shapes reach behind the camera at z = 10, the near plane sits at z = 9
+        state.spawn((BasicMesh::new(vec![Point3::new(1., 0., 0.), Point3::new(1., 0., 20.)], vec![]), TransformComponent::default()));
+        state.spawn((SimpleMesh::new(
+            vec![Point3::new(-1., -1., 0.), Point3::new(1., -1., 0.), Point3::new(0., 1., 20.)],
+            vec![(0, 1, 2, "red".to_string())],
+        ), TransformComponent::default()));
+
+        state.render_tick();
+        let list = state.resource::<DrawList>().unwrap();
+        let lines: Vec<_> = list.items().iter().filter_map(|item| match &item.primitive {
+            Primitive::Line { from, to, .. } => Some((*from, *to)),
+            _ => None,
+        }).collect();
+        assert_eq!(lines.len(), 1);
+        let (from, to) = lines[0];
+        assert!((from.x - 110.).abs() < 1e-3 && (from.y - 50.).abs() < 1e-3);
+        assert!((to.x - 200.).abs() < 1e-3 && (to.z + 1.).abs() < 1e-4);
+
+        // The cut triangle leaves a quad, drawn as two triangles kept in front of the eye
+        let triangles: Vec<_> = list.items().iter().filter_map(|item| match &item.primitive {
+            Primitive::Triangle { points, .. } => Some(*points),
+            _ => None,
+        }).collect();
+        assert_eq!(triangles.len(), 2);
+        assert!(triangles.iter().flatten().all(|point| point.z >= -1. - 1e-4 && point.z <= 1.));
+    }
+
+    #[test]
+    fn test_no_camera_draws_nothing() {
+        let world = World::new();
+        let state = &world.state;
+        state.register_renderer("draw_list", RenderingSystem::new()).unwrap();
+        state.spawn((BasicMesh::new(vec![Point3::origin(), Point3::new(1., 0., 0.)], vec![]), TransformComponent::default()));
+        state.render_tick();
+        assert!(state.resource::<DrawList>().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::Write;
+
+use crate::core::*;
+use crate::ecs::*;
+
+/// Standalone SVG document of the last render tick, written by `SvgSystem`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Svg(pub String);
+
+/// Vector backend writing the `DrawList` into the `Svg` resource
+///
+/// Register it after `RenderingSystem`. Items keep the painter order of the list,
+/// connected lines of the same stroke are merged into one `<polyline>`.
+///
+/// ```ignore
+/// world.register_renderer("draw_list", RenderingSystem::new())?;
+/// world.register_renderer("svg", SvgSystem::new())?;
+/// world.render_tick();
+/// std::fs::write("scene.svg", &world.resource::<Svg>().unwrap().0)?;
+/// ```
+pub struct SvgSystem {
+    pub background: Option<String>,
+    /// Used where a primitive leaves its stroke to the backend
+    pub stroke: String,
+    pub font_size: f32,
+}
+
+impl SvgSystem {
+    pub fn new() -> Self {
+        Self {
+            background: None,
+            stroke: "black".to_string(),
+            font_size: 12.,
+        }
+    }
+
+    /// Serialize the list without touching the world
+    pub fn render(&self, list: &DrawList) -> String {
+        let Viewport { width, height } = list.viewport;
+        let mut svg = String::new();
+        let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#, w = width, h = height);
+        if let Some(background) = &self.background {
+            let _ = writeln!(svg, r#"  <rect width="100%" height="100%" fill="{}"/>"#, escape(background));
+        }
+
+        let items = list.items();
+        let mut i = 0;
+        while i < items.len() {
+            match &items[i].primitive {
+                Primitive::Line { from, to, stroke } => {
+                    let mut points = vec![from.xy(), to.xy()];
+                    while let Some(Primitive::Line { from, to, stroke: next }) = items.get(i + 1).map(|item| &item.primitive) {
+                        if next != stroke || from.xy() != points[points.len() - 1] {
+                            break;
+                        }
+                        points.push(to.xy());
+                        i += 1;
+                    }
+                    let _ = writeln!(svg, r#"  <polyline points="{}" fill="none" stroke="{}"/>"#, format_points(&points), self.color(stroke));
+                }
+                Primitive::Triangle { points, fill } => {
+                    let points: Vec<Point2<f32>> = points.iter().map(|point| point.xy()).collect();
+                    let _ = writeln!(svg, r#"  <polygon points="{}" fill="{}"/>"#, format_points(&points), self.color(fill));
+                }
+                Primitive::Polygon { points, fill, stroke } => {
+                    let (fill, stroke) = self.paint(fill, stroke);
+                    let _ = writeln!(svg, r#"  <polygon points="{}" fill="{}" stroke="{}"/>"#, format_points(points), fill, stroke);
+                }
+                Primitive::Circle { center, radius, fill, stroke } => {
+                    let (fill, stroke) = self.paint(fill, stroke);
+                    let _ = writeln!(svg, r#"  <circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}" stroke="{}"/>"#, center.x, center.y, radius, fill, stroke);
+                }
+                Primitive::Text { position, text } => {
+                    let _ = writeln!(
+                        svg,
+                        r#"  <text x="{:.2}" y="{:.2}" font-size="{}" text-anchor="middle" dominant-baseline="middle" fill="{}">{}</text>"#,
+                        position.x, position.y, self.font_size, escape(&self.stroke), escape(text),
+                    );
+                }
+            }
+            i += 1;
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn color(&self, name: &Option<String>) -> String {
+        escape(name.as_deref().unwrap_or(&self.stroke))
+    }
+
+    /// Fill and stroke attributes, an unpainted shape falls back to the default stroke
+    fn paint(&self, fill: &Option<String>, stroke: &Option<String>) -> (String, String) {
+        let fill_attr = fill.as_deref().map_or_else(|| "none".to_string(), escape);
+        let stroke_attr = match stroke {
+            Some(stroke) => escape(stroke),
+            None if fill.is_none() => escape(&self.stroke),
+            None => "none".to_string(),
+        };
+        (fill_attr, stroke_attr)
+    }
+}
+
+impl Default for SvgSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl System for SvgSystem {
+    fn tick(&mut self, ctx: &SystemContext) {
+        let document = match ctx.world.resource::<DrawList>() {
+            Some(list) => Svg(self.render(&list)),
+            None => return,
+        };
+        ctx.world.insert_resource(document);
+    }
+}
+
+fn format_points(points: &[Point2<f32>]) -> String {
+    points.iter().map(|point| format!("{:.2},{:.2}", point.x, point.y)).collect::<Vec<_>>().join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_svg_document() {
+        let mut list = DrawList::new(Viewport::new(100., 50.));
+        list.push(0.5, Primitive::Line { from: Point3::new(0., 0., 0.5), to: Point3::new(10., 0., 0.4), stroke: None });
+        list.push(0.5, Primitive::Line { from: Point3::new(10., 0., 0.6), to: Point3::new(10., 10., 0.5), stroke: None });
+        let triangle = [Point3::new(0., 0., 0.9), Point3::new(1., 0., 0.9), Point3::new(0., 1., 0.9)];
+        list.push(0.9, Primitive::Triangle { points: triangle, fill: Some("red".to_string()) });
+        list.push(0.1, Primitive::Text { position: Point2::new(5., 5.), text: "a < b".to_string() });
+        list.sort();
+
+        let svg = SvgSystem::new().render(&list);
+        let lines: Vec<&str> = svg.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50""#));
+        assert_eq!(lines[1], r#"  <polygon points="0.00,0.00 1.00,0.00 0.00,1.00" fill="red"/>"#);
+        assert_eq!(lines[2], r#"  <polyline points="0.00,0.00 10.00,0.00 10.00,10.00" fill="none" stroke="black"/>"#);
+        assert!(lines[3].ends_with(">a &lt; b</text>"));
+        assert_eq!(lines[4], "</svg>");
+    }
+}
@@ -0,0 +1,19 @@
+use crate::ecs::*;
+
+/// Propagates `TransformComponent` down the scene hierarchy into `GlobalTransform`
+///
+/// Registered by `World::new` in `SystemStage::PostUpdate`.
+#[derive(Default)]
+pub struct TransformSystem {}
+
+impl TransformSystem {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl System for TransformSystem {
+    fn tick(&mut self, ctx: &SystemContext) {
+        ctx.world.propagate_transforms();
+    }
+}
@@ -0,0 +1,173 @@
+/// Frame timing fed into `WorldState::tick`
+///
+/// `delta` and `elapsed` are scaled by the time scale and stand still while paused.
+/// Systems in `SystemStage::FixedUpdate` receive a copy whose `delta` is the fixed step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Time {
+    delta: f32,
+    raw_delta: f32,
+    elapsed: f64,
+    frame_count: u64,
+    time_scale: f32,
+    paused: bool,
+    fixed_delta: f32,
+    fixed_elapsed: f64,
+    accumulator: f32,
+    max_delta: f32,
+}
+
+impl Time {
+    pub fn new() -> Self {
+        Self {
+            delta: 0.,
+            raw_delta: 0.,
+            elapsed: 0.,
+            frame_count: 0,
+            time_scale: 1.,
+            paused: false,
+            fixed_delta: 1. / 60.,
+            fixed_elapsed: 0.,
+            accumulator: 0.,
+            max_delta: 0.25,
+        }
+    }
+
+    /// Scaled seconds since the last tick
+    #[inline]
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Unscaled seconds since the last tick, also counted while paused
+    #[inline]
+    pub fn raw_delta(&self) -> f32 {
+        self.raw_delta
+    }
+
+    /// Scaled seconds since the world started
+    #[inline]
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    #[inline]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    #[inline]
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = scale.max(0.);
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Length of a fixed simulation step in seconds
+    #[inline]
+    pub fn fixed_delta(&self) -> f32 {
+        self.fixed_delta
+    }
+
+    pub fn set_fixed_delta(&mut self, step: f32) {
+        assert!(step > 0., "fixed step must be positive");
+        self.fixed_delta = step;
+    }
+
+    /// Longest frame accounted for, slower frames are clamped to avoid a spiral of fixed steps
+    pub fn set_max_delta(&mut self, max_delta: f32) {
+        assert!(max_delta > 0., "max delta must be positive");
+        self.max_delta = max_delta;
+    }
+
+    /// Interpolation factor between the last two fixed steps, in `[0, 1)`
+    #[inline]
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.fixed_delta
+    }
+
+    /// Advance by the frame duration and return how many fixed steps are due
+    pub fn advance(&mut self, raw_delta: f32) -> u32 {
+        self.frame_count += 1;
+        self.raw_delta = raw_delta.max(0.);
+        self.delta = if self.paused {
+            0.
+        } else {
+            self.raw_delta.min(self.max_delta) * self.time_scale
+        };
+        self.elapsed += self.delta as f64;
+        self.accumulator += self.delta;
+
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_delta {
+            self.accumulator -= self.fixed_delta;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Copy seen by fixed step systems, advancing the fixed clock by one step
+    pub(crate) fn fixed_step(&mut self) -> Time {
+        self.fixed_elapsed += self.fixed_delta as f64;
+        Time {
+            delta: self.fixed_delta,
+            elapsed: self.fixed_elapsed,
+            ..*self
+        }
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_steps_and_alpha() {
+        let mut time = Time::new();
+        time.set_fixed_delta(0.0625);
+        assert_eq!(time.advance(0.15625), 2);
+        assert_eq!(time.alpha(), 0.5);
+        assert_eq!(time.advance(0.03125), 1);
+        assert_eq!(time.frame_count(), 2);
+
+        time.pause();
+        assert_eq!(time.advance(1.), 0);
+        assert_eq!(time.delta(), 0.);
+        assert_eq!(time.raw_delta(), 1.);
+
+        time.resume();
+        time.set_time_scale(2.);
+        assert_eq!(time.advance(0.0625), 2);
+        assert_eq!(time.elapsed(), 0.3125);
+
+        // Frames longer than the max delta are clamped
+        time.set_time_scale(1.);
+        assert_eq!(time.advance(10.), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "max delta must be positive")]
+    fn test_max_delta_rejects_nan() {
+        Time::new().set_max_delta(f32::NAN);
+    }
+}
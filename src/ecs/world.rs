@@ -1,107 +1,292 @@
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
-use std::collections::{HashMap, BTreeMap};
-use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::any::{type_name, Any, TypeId};
 
 use crate::ecs::entity::*;
+use crate::ecs::signature::Signature;
+use crate::ecs::storage::SparseSet;
+use crate::ecs::borrow::*;
+use crate::ecs::time::Time;
+use crate::ecs::resource::*;
+use crate::ecs::event::Events;
+use crate::ecs::commands::*;
+use crate::ecs::name::{NameIndex, is_indexed};
+use crate::ecs::bundle::Prefab;
+use crate::ecs::snapshot::Snapshotter;
+use crate::error::{Error, Result};
+use crate::ecs::query::*;
 use crate::ecs::component::*;
 use crate::ecs::system::*;
 use crate::ecs::components::*;
 use crate::ecs::stage::*;
-use crate::core::Shape;
+use crate::ecs::systems::transform::TransformSystem;
+use crate::core::{Shape, CameraView, Viewport};
 
-pub type EntityComponentCollection = HashMap<u32, Box<dyn Any>>;
+pub type EntityComponentCollection = HashMap<Entity, Box<dyn Any>>;
+pub type EventUpdaters = HashMap<TypeId, fn(&WorldState)>;
 
 pub struct WorldState {
-    pub entity_store: RefCell<HashMap<u32, Entity>>,
+    pub entity_store: RefCell<SparseSet<EntityEntry>>,
     pub entity_manager: RefCell<EntityManager>,
     pub component_store: ComponentStore,
     pub component_manager: RefCell<ComponentManager>,
     pub system_store: SystemStore,
     pub stage_store: StageStore,
-    pub current_stage: RefCell<(String, Box<dyn Stage>)>,
-    pub renderer_store: RefCell<BTreeMap<String, Box<dyn System>>>,
-    pub active_camera: Cell<u32>,
+    stage_stack: RefCell<Vec<ActiveStage>>,
+    // Transitions requested while a stage hook runs, applied once it returns
+    pending_transitions: RefCell<VecDeque<Trans>>,
+    stage_busy: Cell<bool>,
+    pub running: Cell<bool>,
+    pub active_camera: Cell<Option<Entity>>,
     pub shape_store: RefCell<Vec<Shape>>,
+    pub resource_store: ResourceStore,
+    pub event_updaters: RefCell<EventUpdaters>,
+    pub command_queue: CommandQueue,
+    pub name_index: RefCell<NameIndex>,
+    pub prefabs: RefCell<HashMap<String, Rc<Prefab>>>,
+    pub snapshotters: RefCell<HashMap<TypeId, Snapshotter>>,
+    #[cfg(feature = "serialize")]
+    pub scene_registry: RefCell<crate::ecs::scene::SceneRegistry>,
+    change_tick: Cell<u64>,
+    last_run: Cell<u64>,
 }
 
 impl WorldState {
     pub fn new() -> Rc<Self> {
-        Rc::new(Self {
-            entity_store: RefCell::new(HashMap::new()),
+        let state = Rc::new(Self {
+            entity_store: RefCell::new(SparseSet::new()),
             entity_manager: RefCell::new(EntityManager::new()),
             component_store: ComponentStoreProto::new(),
             component_manager: RefCell::new(ComponentManager::new()),
             system_store: SystemStoreProto::new(),
             stage_store: StageStoreProto::new(),
-            current_stage: RefCell::new((String::from("default"), Box::new(DefaultStage::new()))),
-            renderer_store: RefCell::new(BTreeMap::new()),
-            active_camera: Cell::new(0),
+            stage_stack: RefCell::new(vec![
+                (String::from("default"), Rc::new(RefCell::new(Box::new(DefaultStage::new()) as Box<dyn Stage>)))
+            ]),
+            pending_transitions: RefCell::new(VecDeque::new()),
+            stage_busy: Cell::new(false),
+            running: Cell::new(true),
+            active_camera: Cell::new(None),
             shape_store: RefCell::new(Vec::new()),
-        })
+            resource_store: ResourceStoreProto::new(),
+            event_updaters: RefCell::new(HashMap::new()),
+            command_queue: CommandQueue::new(),
+            name_index: RefCell::new(NameIndex::new()),
+            prefabs: RefCell::new(HashMap::new()),
+            snapshotters: RefCell::new(HashMap::new()),
+            #[cfg(feature = "serialize")]
+            scene_registry: RefCell::new(crate::ecs::scene::default_registry()),
+            change_tick: Cell::new(1),
+            last_run: Cell::new(0),
+        });
+        state.insert_resource(Time::new());
+        state
     }
 
-    pub fn enter<S: 'static + Stage>(&self, name: &str, stage: S) {
-        // Exit the current stage
-        {
-            let mut current_stage = self.current_stage.borrow_mut();
-            current_stage.1.as_mut().on_exit();
-        }
+    /// Register the stage and switch to it
+    pub fn enter<S: 'static + Stage>(&self, name: &str, stage: S) -> Result<()> {
+        self.register_stage(name, stage);
+        self.switch_stage(name)
+    }
 
-        // Popout the stage and swap with the old one
-        let mut store = self.stage_store.borrow_mut();
-        let old_stage = self.current_stage.replace((name.to_string(), Box::new(stage)));
-        store.save_stage(&old_stage.0, old_stage.1);
-        // Enter the new stage
-        {
-            let mut current_stage = self.current_stage.borrow_mut();
-            current_stage.1.as_mut().on_enter();
+    /// Replace the active stage with a registered one, see `switch_stage`
+    pub fn enter_stage(&self, name: &str) -> Result<()> {
+        self.switch_stage(name)
+    }
+
+    /// Pause the active stage and enter the registered stage on top of it
+    ///
+    /// Stage hooks may call this and the other stack changes, the change is then
+    /// applied once the hook returns.
+    pub fn push_stage(&self, name: &str) -> Result<()> {
+        self.apply_transition(Trans::Push(name.to_string()))
+    }
+
+    /// Exit the active stage and resume the one below, stopping the world once the stack is empty
+    ///
+    /// The popped stage goes back to the stage store and can be entered again.
+    /// Returns `None` if the stack was empty or the pop was queued by a stage hook.
+    pub fn pop_stage(&self) -> Option<String> {
+        let name = if self.stage_busy.get() { None } else { self.current_stage() };
+        // Popping never fails, errors would come from transitions queued by its hooks
+        let _ = self.apply_transition(Trans::Pop);
+        name
+    }
+
+    /// Exit the active stage and enter the registered stage in its place
+    pub fn switch_stage(&self, name: &str) -> Result<()> {
+        self.apply_transition(Trans::Switch(name.to_string()))
+    }
+
+    /// Exit every stage from the top down without resuming any of them and stop the world
+    pub fn quit(&self) {
+        let _ = self.apply_transition(Trans::Quit);
+    }
+
+    /// False once the world quit or its stage stack ran empty
+    pub fn is_running(&self) -> bool {
+        self.running.get()
+    }
+
+    /// Name of the active stage
+    pub fn current_stage(&self) -> Option<String> {
+        self.stage_stack.borrow().last().map(|(name, _)| name.clone())
+    }
+
+    fn active_stage(&self) -> Option<ActiveStage> {
+        self.stage_stack.borrow().last().cloned()
+    }
+
+    fn enter_on_top(&self, name: &str, stage: Box<dyn Stage>) {
+        let stage = Rc::new(RefCell::new(stage));
+        self.stage_stack.borrow_mut().push((name.to_string(), stage.clone()));
+        self.running.set(true);
+        stage.borrow_mut().on_enter(self);
+    }
+
+    fn exit_top(&self) -> Option<String> {
+        let (name, stage) = self.stage_stack.borrow_mut().pop()?;
+        stage.borrow_mut().on_exit(self);
+        if stage.borrow().cleanup_on_exit() {
+            self.despawn_stage_entities(&name);
         }
+        // Hooks queue their transitions, so no clone of a stack entry outlives its hook
+        match Rc::try_unwrap(stage) {
+            Ok(stage) => self.stage_store.borrow_mut().save_stage(&name, stage.into_inner()),
+            Err(_) => unreachable!("stage {} is still shared after exiting", name),
+        }
+        Some(name)
     }
 
-    pub fn enter_stage(&self, name: &str) {
-        // Exit the current stage
-        {
-            let mut current_stage = self.current_stage.borrow_mut();
-            current_stage.1.as_mut().on_exit();
+    /// Entities created while the named stage was active
+    pub fn stage_entities(&self, name: &str) -> Vec<Entity> {
+        match self.components::<StageOwned>() {
+            Some(owned) => owned.iter().filter(|(_, owner)| owner.0 == name).map(|(entity, _)| *entity).collect(),
+            None => Vec::new(),
         }
+    }
 
-        // Popout the stage and swap with the old one
-        let mut store = self.stage_store.borrow_mut();
-        if let Some(stage) = store.unregister(name) {
-            let old_stage = self.current_stage.replace((name.to_string(), stage.into_inner()));
-            store.save_stage(&old_stage.0, old_stage.1);
+    /// Despawn the entities owned by the stage that are not `Persistent`, returns how many were removed
+    pub fn despawn_stage_entities(&self, name: &str) -> usize {
+        self.stage_entities(name).into_iter()
+            .filter(|entity| !self.has_component::<Persistent>(*entity))
+            .filter(|entity| self.despawn(*entity))
+            .count()
+    }
+
+    /// Names on the stage stack, bottom first
+    pub fn stage_stack(&self) -> Vec<String> {
+        self.stage_stack.borrow().iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Apply the transition and those queued by the hooks it runs, or queue it while a hook runs
+    ///
+    /// Unknown stage names are rejected before queueing, later failures are reported
+    /// by the call that applies the queue.
+    fn apply_transition(&self, trans: Trans) -> Result<()> {
+        if let Trans::Push(name) | Trans::Switch(name) = &trans {
+            if !self.stage_store.borrow().contains(name) {
+                return Err(Error::UnknownStage(name.clone()));
+            }
+        }
+        self.pending_transitions.borrow_mut().push_back(trans);
+        if self.stage_busy.replace(true) {
+            return Ok(());
+        }
+        let mut result = Ok(());
+        loop {
+            let next = self.pending_transitions.borrow_mut().pop_front();
+            match next {
+                Some(trans) => result = result.and(self.change_stage(trans)),
+                None => break,
+            }
         }
+        self.stage_busy.set(false);
+        result
+    }
 
-        // Enter the new stage
-        {
-            let mut current_stage = self.current_stage.borrow_mut();
-            current_stage.1.as_mut().on_enter();
+    fn change_stage(&self, trans: Trans) -> Result<()> {
+        match trans {
+            Trans::None => {}
+            Trans::Push(name) => {
+                let stage = self.stage_store.borrow_mut().take(&name)?;
+                if let Some((_, current)) = self.active_stage() {
+                    current.borrow_mut().on_pause(self);
+                }
+                self.enter_on_top(&name, stage);
+            }
+            Trans::Pop => {
+                if self.exit_top().is_some() {
+                    match self.active_stage() {
+                        Some((_, current)) => current.borrow_mut().on_resume(self),
+                        None => self.running.set(false),
+                    }
+                }
+            }
+            Trans::Switch(name) => {
+                let stage = self.stage_store.borrow_mut().take(&name)?;
+                self.exit_top();
+                self.enter_on_top(&name, stage);
+            }
+            Trans::Quit => {
+                while self.exit_top().is_some() {}
+                self.running.set(false);
+            }
         }
+        Ok(())
     }
 
-    pub fn register_component<C: 'static + Component>(&self) -> u32 {
-        let mut manager = self.component_manager.borrow_mut();
-        let mut store = self.component_store.borrow_mut();
-        store.register::<C>();
-        manager.register_component::<C>()
+    /// Register the component type and return its signature bit
+    ///
+    /// Panics if a new type is registered while the component store is borrowed, see `try_register_component`.
+    pub fn register_component<C: 'static + Component>(&self) -> usize {
+        self.try_register_component::<C>().unwrap_or_else(|err| panic!("{}", err))
     }
 
-    pub fn register_renderer<S: 'static + System>(&self, name: &str, system: S) {
-        let mut store = self.renderer_store.borrow_mut();
-        if !store.contains_key(name) {
-            store.insert(name.to_string(), Box::new(system));
+    /// Known types are looked up through shared borrows, so this only conflicts with live
+    /// queries when `C` is new
+    pub fn try_register_component<C: 'static + Component>(&self) -> Result<usize> {
+        if let Some(comp_id) = self.get_component_id::<C>() {
+            let store = self.component_store.try_borrow().map_err(|_| Error::BorrowConflict("component store"))?;
+            if store.column::<C>().is_some() {
+                return Ok(comp_id);
+            }
         }
+        let mut store = self.component_store.try_borrow_mut().map_err(|_| Error::BorrowConflict("component store"))?;
+        let mut manager = self.component_manager.try_borrow_mut().map_err(|_| Error::BorrowConflict("component manager"))?;
+        store.register::<C>();
+        Ok(manager.register_component::<C>())
     }
 
-    pub fn register_system<S: 'static + System>(&self, name: &str, system: S) {
-        let mut store = self.system_store.borrow_mut();
-        store.register(name, system);
+    /// Register a system ticked by `render_tick`
+    pub fn register_renderer<S: 'static + System>(&self, name: &str, system: S) -> Result<()> {
+        self.add_system(SystemDescriptor::new(name, system).in_stage(SystemStage::Render))
     }
 
-    pub fn unregister_system(&self, name: &str) {
-        let mut store = self.system_store.borrow_mut();
-        store.unregister(name);
+    /// Register a system in `SystemStage::Update`
+    pub fn register_system<S: 'static + System>(&self, name: &str, system: S) -> Result<()> {
+        self.add_system(SystemDescriptor::new(name, system))
+    }
+
+    /// Register a system with explicit stage, ordering and run criteria
+    ///
+    /// Fails with `BorrowConflict` when called from a running system, which may
+    /// still toggle registered systems with `set_system_enabled`.
+    pub fn add_system(&self, descriptor: SystemDescriptor) -> Result<()> {
+        let mut store = self.system_store.try_borrow_mut().map_err(|_| Error::BorrowConflict("system store"))?;
+        store.add(descriptor)
+    }
+
+    /// Fails with `BorrowConflict` when called from a running system, see `add_system`
+    pub fn unregister_system(&self, name: &str) -> Result<()> {
+        let mut store = self.system_store.try_borrow_mut().map_err(|_| Error::BorrowConflict("system store"))?;
+        store.unregister(name).map(|_| ()).ok_or_else(|| Error::UnknownSystem(name.to_string()))
+    }
+
+    /// Pause or resume a system without unregistering it, also allowed while systems run
+    pub fn set_system_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        self.system_store.borrow().set_enabled(name, enabled)
     }
 
     pub fn register_stage<S: 'static + Stage>(&self, name: &str, stage: S) {
@@ -115,57 +300,475 @@ impl WorldState {
     }
 
 
-    pub fn tick(&self) {
-        self.system_store.borrow().tick();
-        self.current_stage.borrow_mut().1.as_mut().tick();
+    /// Advance the world by `delta` seconds of wall clock time
+    ///
+    /// Fixed step systems run once per elapsed `Time::fixed_delta`, the leftover
+    /// fraction is exposed to renderers as `Time::alpha`. The transition returned by the
+    /// active stage is applied at the end of the tick, an unknown stage name is reported
+    /// after the rest of the tick completed.
+    pub fn tick(&self, delta: f32) -> Result<()> {
+        let steps = self.time_mut().advance(delta);
+        let time = self.time();
+        {
+            let store = self.system_store.borrow();
+            store.run(SystemStage::PreUpdate, self, &time);
+            for _ in 0..steps {
+                let fixed = self.time_mut().fixed_step();
+                store.run(SystemStage::FixedUpdate, self, &fixed);
+            }
+            store.run(SystemStage::Update, self, &time);
+            store.run(SystemStage::PostUpdate, self, &time);
+        }
+        let trans = match self.active_stage() {
+            Some((_, stage)) => {
+                let busy = self.stage_busy.replace(true);
+                let trans = stage.borrow_mut().tick(self);
+                self.stage_busy.set(busy);
+                trans
+            }
+            None => Trans::None,
+        };
+        self.apply_commands();
+        self.update_events();
+        self.clear_trackers();
+        self.apply_transition(trans)
+    }
+
+    /// Current world change tick, stamped on components as they are bound or written
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick.get()
+    }
+
+    /// Tick that `Added`, `Changed` and `removed` compare against
+    ///
+    /// While a system ticks this is the tick of its previous run, elsewhere
+    /// it is the end of the previous world tick.
+    pub fn last_run(&self) -> u64 {
+        self.last_run.get()
+    }
+
+    /// Advance the change tick for the next system run
+    pub(crate) fn increment_change_tick(&self) -> u64 {
+        self.change_tick.set(self.change_tick.get() + 1);
+        self.change_tick.get()
+    }
+
+    /// Rewind both ticks, used by `restore`
+    pub(crate) fn set_change_ticks(&self, change_tick: u64, last_run: u64) {
+        self.change_tick.set(change_tick);
+        self.last_run.set(last_run);
+    }
+
+    /// Swap the tick change detection compares against, returning the previous one
+    pub(crate) fn replace_last_run(&self, tick: u64) -> u64 {
+        self.last_run.replace(tick)
+    }
+
+    /// End the change detection frame, removals stay visible until the end of the next world tick
+    fn clear_trackers(&self) {
+        self.component_store.borrow().prune_removed(self.last_run.get());
+        self.last_run.set(self.change_tick.get());
+        self.increment_change_tick();
     }
 
     pub fn render_tick(&self) {
-        for system in self.renderer_store.borrow_mut().values_mut() {
-            system.tick();
+        let time = self.time();
+        self.system_store.borrow().run(SystemStage::Render, self, &time);
+    }
+
+    /// Copy of the current frame timing, kept as the `Time` resource
+    pub fn time(&self) -> Time {
+        *self.resource::<Time>().expect("Time resource was removed")
+    }
+
+    /// Adjust time scale, pause state or the fixed step
+    pub fn time_mut(&self) -> StoreRefMut<'_, Time> {
+        self.resource_mut::<Time>().expect("Time resource was removed")
+    }
+
+    /// Register an event type, its queue is rotated at the end of every tick
+    pub fn add_event<E: 'static>(&self) {
+        if !self.has_resource::<Events<E>>() {
+            self.insert_resource(Events::<E>::new());
+        }
+        self.event_updaters.borrow_mut().entry(TypeId::of::<Events<E>>()).or_insert(|state| {
+            if let Some(mut events) = state.resource_mut::<Events<E>>() {
+                events.update();
+            }
+        });
+    }
+
+    /// Broadcast an event to every `EventReader<E>`, registering the event type on first use
+    pub fn send<E: 'static>(&self, event: E) {
+        if !self.has_resource::<Events<E>>() {
+            self.add_event::<E>();
+        }
+        self.resource_mut::<Events<E>>().unwrap().send(event);
+    }
+
+    /// Borrow the queue of an event type for reading with an `EventReader<E>`
+    pub fn events<E: 'static>(&self) -> Option<StoreRef<'_, Events<E>>> {
+        self.resource::<Events<E>>()
+    }
+
+    pub(crate) fn update_events(&self) {
+        let updaters: Vec<fn(&WorldState)> = self.event_updaters.borrow().values().cloned().collect();
+        for update in updaters {
+            update(self);
+        }
+    }
+
+    /// Insert a global resource, returning the one of the same type it replaced
+    ///
+    /// Panics if any resource is borrowed.
+    pub fn insert_resource<R: 'static>(&self, resource: R) -> Option<R> {
+        self.resource_store.try_borrow_mut()
+            .unwrap_or_else(|_| panic!("{}", Error::BorrowConflict("resource store")))
+            .insert(resource)
+    }
+
+    /// Remove a global resource
+    ///
+    /// Panics if any resource is borrowed.
+    pub fn remove_resource<R: 'static>(&self) -> Option<R> {
+        self.resource_store.try_borrow_mut()
+            .unwrap_or_else(|_| panic!("{}", Error::BorrowConflict("resource store")))
+            .remove::<R>()
+    }
+
+    pub fn has_resource<R: 'static>(&self) -> bool {
+        self.resource_store.borrow().contains::<R>()
+    }
+
+    pub fn try_resource<R: 'static>(&self) -> Result<StoreRef<'_, R>> {
+        // SAFETY: the outer borrow is moved into the returned guard
+        let (outer, store) = unsafe { hold(&self.resource_store) }
+            .map_err(|_| Error::BorrowConflict("resource store"))?;
+        Ok(StoreRef::new(store.try_get::<R>()?, outer))
+    }
+
+    pub fn try_resource_mut<R: 'static>(&self) -> Result<StoreRefMut<'_, R>> {
+        // SAFETY: the outer borrow is moved into the returned guard
+        let (outer, store) = unsafe { hold(&self.resource_store) }
+            .map_err(|_| Error::BorrowConflict("resource store"))?;
+        Ok(StoreRefMut::new(store.try_get_mut::<R>()?, outer))
+    }
+
+    /// Borrow a global resource, `None` if it was never inserted
+    ///
+    /// Panics if the resource is already mutably borrowed, see `try_resource`.
+    pub fn resource<R: 'static>(&self) -> Option<StoreRef<'_, R>> {
+        match self.try_resource::<R>() {
+            Ok(resource) => Some(resource),
+            Err(Error::UnknownResource(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Mutably borrow a global resource, `None` if it was never inserted
+    ///
+    /// Panics if the resource is already borrowed, see `try_resource_mut`.
+    pub fn resource_mut<R: 'static>(&self) -> Option<StoreRefMut<'_, R>> {
+        match self.try_resource_mut::<R>() {
+            Ok(resource) => Some(resource),
+            Err(Error::UnknownResource(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Create an entity owned by the active stage, see `StageOwned`
+    pub fn create_entity(&self) -> Entity {
+        let entity = self.entity_manager.borrow_mut().create_entity();
+        self.insert_entity(entity, self.current_stage());
+        entity
+    }
+
+    /// Make an allocated entity visible to the stores
+    pub(crate) fn insert_entity(&self, entity: Entity, stage: Option<String>) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        self.entity_store.borrow_mut().insert(entity, EntityEntry::new(entity));
+        if let Some(stage) = stage {
+            self.bind_component(entity, StageOwned(stage));
+        }
+    }
+
+    /// Buffer for structural edits that cannot be made while stores are borrowed
+    pub fn commands(&self) -> Commands<'_> {
+        Commands::new(self)
+    }
+
+    /// Apply the recorded commands, the world also does so after every system and stage tick
+    ///
+    /// Also brings the name index up to date with `Name` and `Tags` written through mutable access.
+    pub fn apply_commands(&self) {
+        self.command_queue.apply(self);
+        self.sync_name_index();
+    }
+
+    /// Remove the entity and all of its components, returns false if the handle is stale
+    pub fn despawn(&self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.detach_hierarchy(entity);
+        self.unindex_entity(entity);
+        self.entity_manager.borrow_mut().destroy_entity(entity);
+        self.entity_store.borrow_mut().remove(&entity);
+        self.component_store.borrow().remove_entity(entity, self.change_tick());
+        if self.active_camera.get() == Some(entity) {
+            self.active_camera.set(None);
+        }
+        true
+    }
+
+    #[inline]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entity_manager.borrow().is_alive(entity)
+    }
+
+    pub fn bind_component<C: 'static + Component>(&self, entity: Entity, component: C) -> bool {
+        self.replace_component(entity, component).is_ok()
+    }
+
+    /// Bind the component and hand back the one it replaced, `Err` returns the component if the entity does not exist
+    ///
+    /// Panics if the storage of `C` is borrowed, see `try_bind_component`.
+    pub fn replace_component<C: 'static + Component>(&self, entity: Entity, component: C) -> std::result::Result<Option<C>, C> {
+        if !self.entity_store.borrow().contains_key(&entity) {
+            return Err(component);
+        }
+        Ok(self.try_bind_component(entity, component).unwrap_or_else(|err| panic!("{}", err)))
+    }
+
+    /// Bind the component and hand back the one it replaced
+    ///
+    /// Fails without touching the entity if it does not exist or the storage of `C` is borrowed.
+    pub fn try_bind_component<C: 'static + Component>(&self, entity: Entity, component: C) -> Result<Option<C>> {
+        let comp_id = self.try_register_component::<C>()?;
+        let replaced = {
+            let mut entity_store = self.entity_store.try_borrow_mut().map_err(|_| Error::BorrowConflict("entity store"))?;
+            let entry = entity_store.get_mut(&entity).ok_or(Error::NoSuchEntity(entity))?;
+            let component_store = self.component_store.try_borrow().map_err(|_| Error::BorrowConflict("component store"))?;
+            let replaced = component_store.try_insert(entity, component, self.change_tick())?;
+            entry.components.insert(comp_id);
+            replaced
+        };
+        if is_indexed::<C>() {
+            self.reindex_entity(entity, replaced.as_ref().map(|old| old as &dyn Any));
+        }
+        Ok(replaced)
+    }
+
+    /// Remove the component from the entity, returns false if the entity does not carry it
+    pub fn unbind_component<C: 'static + Component>(&self, entity: Entity) -> bool {
+        self.take_component::<C>(entity).is_some()
+    }
+
+    /// Remove the component from the entity and return it
+    pub fn take_component<C: 'static + Component>(&self, entity: Entity) -> Option<C> {
+        let comp_id = self.get_component_id::<C>()?;
+        let taken = {
+            let mut entity_store = self.entity_store.borrow_mut();
+            let entry = entity_store.get_mut(&entity)?;
+            entry.components.remove(comp_id);
+            self.component_store.borrow().remove::<C>(entity, self.change_tick())
+        };
+        if is_indexed::<C>() {
+            self.reindex_entity(entity, taken.as_ref().map(|old| old as &dyn Any));
+        }
+        taken
+    }
+
+    pub fn has_component<C: 'static + Component>(&self, entity: Entity) -> bool {
+        match self.get_component_id::<C>() {
+            Some(comp_id) => self.entity_store.borrow().get(&entity).is_some_and(|entry| entry.components.contains(comp_id)),
+            None => false,
         }
     }
 
-    pub fn create_entity(&self) -> u32 {
-        let mut manager = self.entity_manager.borrow_mut();
-        let entity = manager.create_entity();
-        let id = entity.id;
-        let mut store = self.entity_store.borrow_mut();
-        store.insert(entity.id, entity);
-        id
+    /// Borrow the whole storage of a component type
+    pub fn try_components<C: 'static + Component>(&self) -> Result<StoreRef<'_, SparseSet<C>>> {
+        // SAFETY: the outer borrow is moved into the returned guard
+        let (outer, store) = unsafe { hold(&self.component_store) }
+            .map_err(|_| Error::BorrowConflict("component store"))?;
+        Ok(StoreRef::new(store.try_get::<C>()?, outer))
     }
 
-    pub fn bind_component<C: 'static + Component>(&self, entity_id: u32, component: C) -> bool {
-        let comp_id = self.register_component::<C>();
-        let mut entity_store = self.entity_store.borrow_mut();
-        let component_store = self.component_store.borrow();
-        if let Some(entity) = entity_store.get_mut(&entity_id) {
-            entity.components &= comp_id;
-            let mut store = component_store.get_mut::<C>();
-            store.insert(entity_id, component);
-            return true;
+    /// Mutably borrow the whole storage of a component type
+    ///
+    /// Writes through the storage are not tracked, see `mark_changed`.
+    pub fn try_components_mut<C: 'static + Component>(&self) -> Result<StoreRefMut<'_, SparseSet<C>>> {
+        let storage = self.storage_mut::<C>()?;
+        if is_indexed::<C>() {
+            self.name_index.borrow_mut().invalidate();
         }
-        false
+        Ok(storage)
+    }
+
+    fn storage_mut<C: 'static + Component>(&self) -> Result<StoreRefMut<'_, SparseSet<C>>> {
+        // SAFETY: the outer borrow is moved into the returned guard
+        let (outer, store) = unsafe { hold(&self.component_store) }
+            .map_err(|_| Error::BorrowConflict("component store"))?;
+        Ok(StoreRefMut::new(store.try_get_mut::<C>()?, outer))
+    }
+
+    fn ticks_mut<C: 'static + Component>(&self) -> Result<StoreRefMut<'_, SparseSet<ComponentTicks>>> {
+        // SAFETY: the outer borrow is moved into the returned guard
+        let (outer, store) = unsafe { hold(&self.component_store) }
+            .map_err(|_| Error::BorrowConflict("component store"))?;
+        Ok(StoreRefMut::new(store.try_ticks_mut::<C>()?, outer))
+    }
+
+    /// Borrow the whole storage of a component type, `None` if the type is not registered
+    ///
+    /// Panics if the storage is already mutably borrowed, see `try_components`.
+    pub fn components<C: 'static + Component>(&self) -> Option<StoreRef<'_, SparseSet<C>>> {
+        match self.try_components::<C>() {
+            Ok(storage) => Some(storage),
+            Err(Error::UnknownComponent(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Mutably borrow the whole storage of a component type, `None` if the type is not registered
+    ///
+    /// Panics if the storage is already borrowed, see `try_components_mut`.
+    pub fn components_mut<C: 'static + Component>(&self) -> Option<StoreRefMut<'_, SparseSet<C>>> {
+        match self.try_components_mut::<C>() {
+            Ok(storage) => Some(storage),
+            Err(Error::UnknownComponent(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Borrow the component of an entity
+    pub fn get_component<C: 'static + Component>(&self, entity: Entity) -> Option<StoreRef<'_, C>> {
+        StoreRef::filter_map(self.components::<C>()?, |storage| storage.get(&entity))
+    }
+
+    /// Mutably borrow the component of an entity, writes through the guard mark it as changed
+    pub fn get_component_mut<C: 'static + Component>(&self, entity: Entity) -> Option<Mut<'_, C>> {
+        match self.try_get_component_mut::<C>(entity) {
+            Ok(component) => Some(component),
+            Err(err @ Error::BorrowConflict(_)) => panic!("{}", err),
+            Err(_) => None,
+        }
+    }
+
+    /// Borrow the component of an entity, reporting why it is not available
+    pub fn try_get_component<C: 'static + Component>(&self, entity: Entity) -> Result<StoreRef<'_, C>> {
+        self.check_entity(entity)?;
+        StoreRef::filter_map(self.try_components::<C>()?, |storage| storage.get(&entity))
+            .ok_or(Error::MissingComponent(entity, type_name::<C>()))
+    }
+
+    /// Mutably borrow the component of an entity, reporting why it is not available
+    pub fn try_get_component_mut<C: 'static + Component>(&self, entity: Entity) -> Result<Mut<'_, C>> {
+        self.check_entity(entity)?;
+        let component = StoreRefMut::filter_map(self.storage_mut::<C>()?, |storage| storage.get_mut(&entity))
+            .ok_or(Error::MissingComponent(entity, type_name::<C>()))?;
+        let ticks = StoreRefMut::filter_map(self.ticks_mut::<C>()?, |ticks| ticks.get_mut(&entity))
+            .ok_or(Error::MissingComponent(entity, type_name::<C>()))?;
+        Ok(Mut::stored(component, ticks, self.change_tick()))
+    }
+
+    /// Flag the component as changed, needed after writing through `components_mut`
+    pub fn mark_changed<C: 'static + Component>(&self, entity: Entity) {
+        self.component_store.borrow().mark_changed::<C>(entity, self.change_tick());
+    }
+
+    /// Whether the component was bound since `last_run`
+    pub fn is_added<C: 'static + Component>(&self, entity: Entity) -> bool {
+        self.component_ticks::<C>(entity).is_some_and(|ticks| ticks.is_added(self.last_run()))
+    }
+
+    /// Whether the component was bound or written through `Mut` since `last_run`
+    pub fn is_changed<C: 'static + Component>(&self, entity: Entity) -> bool {
+        self.component_ticks::<C>(entity).is_some_and(|ticks| ticks.is_changed(self.last_run()))
+    }
+
+    pub fn component_ticks<C: 'static + Component>(&self, entity: Entity) -> Option<ComponentTicks> {
+        self.component_store.borrow().try_ticks::<C>().ok()?.get(&entity).copied()
+    }
+
+    /// Entities that lost the component, by unbinding or despawning, since `last_run`
+    pub fn removed<C: 'static + Component>(&self) -> Vec<Entity> {
+        self.component_store.borrow().removed::<C>(self.last_run())
     }
 
     #[inline]
-    pub fn get_component_id<C: 'static + Component>(&self) -> Option<u32> {
-        match self.component_manager.borrow().get_code::<C>() {
-            Some(id) => Some(id.clone()),
-            None => None
+    fn check_entity(&self, entity: Entity) -> Result<()> {
+        if self.is_alive(entity) {
+            Ok(())
+        } else {
+            Err(Error::NoSuchEntity(entity))
         }
     }
 
-    pub fn switch_camera(&self, camera: u32) {
-        self.active_camera.set(camera);
+    #[inline]
+    pub fn get_component_id<C: 'static + Component>(&self) -> Option<usize> {
+        self.component_manager.borrow().get_code::<C>().copied()
     }
 
-    /* FIXME Is it proper to fetch shared projection?
-    pub fn get_active_camera(&self) ->  {
-        let id = self.active_camera.get();
-        let camera = self.component_store.borrow().get::<CameraComponent>().get(id).unwrap().
+    /// Component signature of a live entity
+    pub fn signature(&self, entity: Entity) -> Option<Signature> {
+        self.entity_store.borrow().get(&entity).map(|entry| entry.components.clone())
+    }
+
+    /// Collect the entities whose signature contains every bit of `signature`
+    pub fn entities_with(&self, signature: &Signature) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = self.entity_store.borrow().values()
+            .filter(|entry| entry.components.contains_all(signature))
+            .map(|entry| entry.entity)
+            .collect();
+        entities.sort();
+        entities
+    }
+
+    /// Borrow all entities matching `Q`, e.g. `query::<(&mut TransformComponent, &MeshComponent)>()`
+    pub fn query<Q: WorldQuery>(&self) -> Result<Query<'_, Q>> {
+        Query::new(self)
+    }
+
+    /// Same as `query` with an extra `With`/`Without` filter
+    pub fn query_filtered<Q: WorldQuery, F: QueryFilter>(&self) -> Result<Query<'_, Q, F>> {
+        Query::new(self)
+    }
+
+    pub fn switch_camera(&self, camera: Entity) {
+        self.active_camera.set(Some(camera));
+    }
+
+    /// Store the new `Viewport` and match the aspect of the active camera to it
+    ///
+    /// Empty or non-finite sizes, such as a minimized window, keep the previous viewport.
+    pub fn resize_viewport(&self, width: f32, height: f32) {
+        if !(width > 0. && width.is_finite() && height > 0. && height.is_finite()) {
+            return;
+        }
+        let viewport = Viewport::new(width, height);
+        self.insert_resource(viewport);
+        if let Some(mut camera) = self.active_camera.get().and_then(|entity| self.get_component_mut::<CameraComponent>(entity)) {
+            camera.set_aspect(viewport.aspect());
+        }
+    }
+
+    /// View and projection of the active camera entity
+    ///
+    /// Fails if no camera is active, the entity is gone or lost its `CameraComponent`,
+    /// or its world transform can not be inverted.
+    pub fn get_active_camera(&self) -> Result<CameraView> {
+        let entity = self.active_camera.get().ok_or(Error::NoActiveCamera)?;
+        if !self.is_alive(entity) {
+            return Err(Error::NoSuchEntity(entity));
+        }
+        let transform = self.world_matrix(entity);
+        let camera = self.get_component::<CameraComponent>(entity)
+            .ok_or(Error::MissingComponent(entity, type_name::<CameraComponent>()))?;
+        CameraView::new(&camera, &transform).ok_or(Error::SingularCamera(entity))
     }
-    */
 }
 
 #[derive(Clone)]
@@ -181,6 +784,17 @@ impl World {
         state.register_component::<CameraComponent>();
         state.register_component::<ShapeComponent>();
         state.register_component::<WidgetComponent>();
+        state.register_component::<Parent>();
+        state.register_component::<Children>();
+        state.register_component::<GlobalTransform>();
+        state.register_component::<StageOwned>();
+        state.register_component::<Persistent>();
+        state.register_component::<Name>();
+        state.register_component::<Tags>();
+        crate::ecs::snapshot::register_defaults(&state);
+        state.add_system(
+            SystemDescriptor::new("transform", TransformSystem::new()).in_stage(SystemStage::PostUpdate)
+        ).unwrap();
 
         Self {
             state,
@@ -199,3 +813,169 @@ impl World {
     }
 }
 
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_despawn_clears_components() {
+        let world = World::new();
+        let state = &world.state;
+        let entity = state.create_entity();
+        state.bind_component(entity, TransformComponent::default());
+        assert!(state.despawn(entity));
+        assert!(!state.is_alive(entity));
+        assert!(!state.despawn(entity));
+        assert!(state.component_store.borrow().get::<TransformComponent>().is_empty());
+
+        // The recycled slot must not be reachable through the stale handle
+        let recycled = state.create_entity();
+        assert_eq!(recycled.index(), entity.index());
+        assert!(!state.bind_component(entity, TransformComponent::default()));
+        assert!(state.bind_component(recycled, TransformComponent::default()));
+    }
+
+    #[test]
+    fn test_component_crud() {
+        let world = World::new();
+        let state = &world.state;
+        let entity = state.create_entity();
+        assert!(!state.has_component::<TransformComponent>(entity));
+        assert!(matches!(state.replace_component(entity, TransformComponent::default()), Ok(None)));
+        assert!(state.has_component::<TransformComponent>(entity));
+
+        state.get_component_mut::<TransformComponent>(entity).unwrap().set_translation_xyz(1., 2., 3.);
+        let mut moved = TransformComponent::default();
+        moved.set_translation_xyz(4., 5., 6.);
+        let old = state.replace_component(entity, moved).ok().flatten().unwrap();
+        assert_eq!(old.translation().x, 1.);
+        assert_eq!(state.get_component::<TransformComponent>(entity).unwrap().translation().x, 4.);
+
+        let taken = state.take_component::<TransformComponent>(entity).unwrap();
+        assert_eq!(taken.translation().z, 6.);
+        assert!(!state.has_component::<TransformComponent>(entity));
+        assert!(!state.unbind_component::<TransformComponent>(entity));
+        assert!(state.get_component::<TransformComponent>(entity).is_none());
+
+        assert_eq!(
+            state.try_get_component::<TransformComponent>(entity).err(),
+            Some(Error::MissingComponent(entity, type_name::<TransformComponent>()))
+        );
+        state.despawn(entity);
+        assert!(state.replace_component(entity, TransformComponent::default()).is_err());
+        assert_eq!(state.try_get_component::<TransformComponent>(entity).err(), Some(Error::NoSuchEntity(entity)));
+    }
+
+    #[test]
+    fn test_bind_during_query() {
+        let world = World::new();
+        let state = &world.state;
+        let entity = state.create_entity();
+        state.bind_component(entity, TransformComponent::default());
+
+        let mut query = state.query::<&TransformComponent>().unwrap();
+        assert_eq!(query.iter().count(), 1);
+        // Other registered types bind fine, the borrowed one and new types report the conflict
+        assert!(state.bind_component(entity, Persistent));
+        assert_eq!(
+            state.try_bind_component(entity, TransformComponent::default()).err(),
+            Some(Error::BorrowConflict(type_name::<TransformComponent>()))
+        );
+        struct Fresh;
+        impl Component for Fresh {}
+        assert_eq!(state.try_bind_component(entity, Fresh).err(), Some(Error::BorrowConflict("component store")));
+        drop(query);
+        assert!(state.has_component::<Persistent>(entity));
+        assert!(state.try_bind_component(entity, Fresh).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_resources() {
+        struct Score(u32);
+
+        let state = WorldState::new();
+        assert!(state.resource::<Score>().is_none());
+        assert!(state.insert_resource(Score(1)).is_none());
+        state.resource_mut::<Score>().unwrap().0 += 1;
+        {
+            let score = state.resource::<Score>().unwrap();
+            assert_eq!(score.0, 2);
+            assert_eq!(state.try_resource_mut::<Score>().err(), Some(Error::BorrowConflict(type_name::<Score>())));
+        }
+        assert_eq!(state.insert_resource(Score(5)).unwrap().0, 2);
+        assert_eq!(state.remove_resource::<Score>().unwrap().0, 5);
+        assert!(!state.has_resource::<Score>());
+
+        state.tick(0.5).unwrap();
+        assert_eq!(state.resource::<Time>().unwrap().frame_count(), 1);
+    }
+
+    #[test]
+    fn test_active_camera() {
+        let world = World::new();
+        let state = &world.state;
+        assert_eq!(state.get_active_camera().err(), Some(Error::NoActiveCamera));
+
+        world.attach_default_camera();
+        let camera = state.active_camera.get().unwrap();
+        let view = state.get_active_camera().unwrap();
+        assert_eq!(view.view.transform_point(&crate::core::Point3::new(0., 0., 300.)), crate::core::Point3::origin());
+        state.resize_viewport(400., 200.);
+        assert_eq!(state.get_component::<CameraComponent>(camera).unwrap().aspect(), 2.);
+        assert_eq!(state.resource::<Viewport>().unwrap().width, 400.);
+        state.resize_viewport(400., 0.);
+        assert_eq!(state.get_component::<CameraComponent>(camera).unwrap().aspect(), 2.);
+        assert_eq!(state.resource::<Viewport>().unwrap().height, 200.);
+
+        state.unbind_component::<CameraComponent>(camera);
+        assert_eq!(state.get_active_camera().err(), Some(Error::MissingComponent(camera, type_name::<CameraComponent>())));
+        let gone = state.create_entity();
+        state.despawn(gone);
+        state.switch_camera(gone);
+        assert_eq!(state.get_active_camera().err(), Some(Error::NoSuchEntity(gone)));
+    }
+
+    #[test]
+    fn test_events_live_for_two_ticks() {
+        use crate::ecs::event::EventReader;
+
+        let state = WorldState::new();
+        let mut reader = EventReader::<&str>::new();
+        state.send("hit");
+        state.tick(0.).unwrap();
+        state.send("miss");
+        assert_eq!(reader.read(&state.events::<&str>().unwrap()).cloned().collect::<Vec<_>>(), vec!["hit", "miss"]);
+
+        let mut late = EventReader::<&str>::new();
+        state.tick(0.).unwrap();
+        assert_eq!(late.read(&state.events::<&str>().unwrap()).cloned().collect::<Vec<_>>(), vec!["miss"]);
+        state.tick(0.).unwrap();
+        assert!(state.events::<&str>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_signature_tracks_bindings() {
+        let world = World::new();
+        let state = &world.state;
+        let mesh_id = state.get_component_id::<MeshComponent>().unwrap();
+        let transform_id = state.get_component_id::<TransformComponent>().unwrap();
+
+        let a = state.create_entity();
+        let b = state.create_entity();
+        state.bind_component(a, TransformComponent::default());
+        state.bind_component(b, TransformComponent::default());
+        state.bind_component(b, crate::core::BasicMesh::new(Vec::new(), Vec::new()));
+
+        let signature = Signature::from_ids(&[mesh_id, transform_id]);
+        assert_eq!(state.entities_with(&signature), vec![b]);
+        assert_eq!(state.entities_with(&Signature::new().with(transform_id)), vec![a, b]);
+        assert!(state.signature(a).unwrap().contains(transform_id));
+    }
+}
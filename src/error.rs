@@ -0,0 +1,63 @@
+use std::error;
+use std::fmt;
+use std::result;
+
+use crate::ecs::entity::Entity;
+
+/// Errors reported by the fallible world and store accessors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The component type was never registered in the world
+    UnknownComponent(&'static str),
+    /// No system was registered under the name
+    UnknownSystem(String),
+    /// A system with the same name is already registered
+    DuplicateSystem(String),
+    /// The before/after constraints of the system form a cycle
+    SystemCycle(String),
+    /// No stage was registered under the name
+    UnknownStage(String),
+    /// No prefab was registered under the name
+    UnknownPrefab(String),
+    /// A scene could not be read, written or loaded
+    Scene(String),
+    /// No resource of the type was inserted
+    UnknownResource(&'static str),
+    /// The store is already borrowed in a conflicting way
+    BorrowConflict(&'static str),
+    /// The entity handle is stale or was never created
+    NoSuchEntity(Entity),
+    /// Parenting the entity would make it its own ancestor
+    HierarchyCycle(Entity),
+    /// The entity exists but does not carry the component
+    MissingComponent(Entity, &'static str),
+    /// `WorldState::active_camera` is not set
+    NoActiveCamera,
+    /// The world transform of the camera entity can not be inverted into a view
+    SingularCamera(Entity),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownComponent(name) => write!(f, "component {} is not registered", name),
+            Error::UnknownSystem(name) => write!(f, "system {} is not registered", name),
+            Error::DuplicateSystem(name) => write!(f, "system {} is already registered", name),
+            Error::SystemCycle(name) => write!(f, "ordering of system {} forms a cycle", name),
+            Error::UnknownStage(name) => write!(f, "stage {} is not registered", name),
+            Error::UnknownPrefab(name) => write!(f, "prefab {} is not registered", name),
+            Error::Scene(message) => write!(f, "scene error: {}", message),
+            Error::UnknownResource(name) => write!(f, "resource {} is not inserted", name),
+            Error::BorrowConflict(name) => write!(f, "{} is already borrowed", name),
+            Error::NoSuchEntity(entity) => write!(f, "entity {} does not exist", entity),
+            Error::HierarchyCycle(entity) => write!(f, "entity {} can not be its own ancestor", entity),
+            Error::MissingComponent(entity, name) => write!(f, "entity {} has no component {}", entity, name),
+            Error::NoActiveCamera => write!(f, "no camera is active"),
+            Error::SingularCamera(entity) => write!(f, "transform of camera {} can not be inverted", entity),
+        }
+    }
+}
+
+impl error::Error for Error {}
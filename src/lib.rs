@@ -1,8 +1,10 @@
 #[macro_use] pub mod traits;
 #[macro_use] pub mod utils;
 
+pub mod error;
 pub mod core;
 pub mod ecs;
 
+pub use error::{Error, Result};
 pub use ecs::{ World, WorldState };
 